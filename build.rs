@@ -25,6 +25,30 @@ fn build_shaders() {
         &pt("src/shaders/shader.frag"),
         ShaderKind::Fragment,
     );
+    build_shader(
+        &mut compiler,
+        &format!("{}/multi.frag.spirv", out_dir),
+        &pt("src/shaders/multi.frag"),
+        ShaderKind::Fragment,
+    );
+    build_shader(
+        &mut compiler,
+        &format!("{}/palette.frag.spirv", out_dir),
+        &pt("src/shaders/palette.frag"),
+        ShaderKind::Fragment,
+    );
+    build_shader(
+        &mut compiler,
+        &format!("{}/debug_uv.frag.spirv", out_dir),
+        &pt("src/shaders/debug_uv.frag"),
+        ShaderKind::Fragment,
+    );
+    build_shader(
+        &mut compiler,
+        &format!("{}/overdraw.frag.spirv", out_dir),
+        &pt("src/shaders/overdraw.frag"),
+        ShaderKind::Fragment,
+    );
 }
 
 fn build_shader(compiler: &mut Compiler, out_path: &str, path: &str, kind: ShaderKind) {
@@ -27,6 +27,7 @@ fn main() {
     ))
     .unwrap();
     graphics.set_scale([logical_size.width as f32, logical_size.height as f32]);
+    graphics.set_scale_factor(window.scale_factor() as f32);
 
     let TextGridDim { nrows, ncols } = graphics.init_text_grid(80).unwrap();
 
@@ -81,11 +82,13 @@ fn main() {
                     graphics.resized(physical_size.width, physical_size.height);
                     graphics.set_scale([logical_size.width, logical_size.height]);
                 }
-                WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
-                    // TODO: update scale factor and size
+                WindowEvent::ScaleFactorChanged {
+                    scale_factor,
+                    new_inner_size,
+                } => {
                     let physical_size = **new_inner_size;
-                    let logical_size =
-                        LogicalSize::from_physical(physical_size, window.scale_factor());
+                    let logical_size = LogicalSize::from_physical(physical_size, *scale_factor);
+                    graphics.set_scale_factor(*scale_factor as f32);
                     graphics.resized(physical_size.width, physical_size.height);
                     graphics.set_scale([logical_size.width, logical_size.height]);
                 }
@@ -0,0 +1,66 @@
+//! Measures how long it takes to draw 50k sprites, e.g. to compare
+//! a change to the renderer against the commit before it.
+//! Run with `cargo run --release --example instances`.
+//!
+//! Each frame is drawn with `read_pixel`, which renders offscreen and waits
+//! for the GPU to finish, so the times aren't capped by vsync.
+//! They include uploading the instances, since layers are uploaded every render.
+use a2d::Graphics2D;
+use a2d::Sheet;
+use futures::executor::block_on;
+use std::time::Duration;
+use std::time::Instant;
+use winit::{dpi::PhysicalSize, event_loop::EventLoop, window::WindowBuilder};
+
+const NINSTANCES: usize = 50_000;
+const WARMUP_FRAMES: usize = 10;
+const NFRAMES: usize = 200;
+
+fn main() {
+    let width = 800;
+    let height = 600;
+
+    // wgpu needs a window to pick an adapter, but it never has to be shown
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_inner_size(PhysicalSize { width, height })
+        .with_visible(false)
+        .build(&event_loop)
+        .unwrap();
+
+    let mut graphics = block_on(Graphics2D::new(width, height, &window)).unwrap();
+    graphics.set_scale([width as f32, height as f32]);
+    let sheet = Sheet::from_color(&mut graphics, [0.3, 0.6, 1.0]).unwrap();
+
+    // Small sprites, so that the time goes into per-vertex work
+    // rather than filling pixels
+    let layer = graphics.add_layer("instances");
+    for i in 0..NINSTANCES {
+        let x = (i * 37) as f32 % width as f32;
+        let y = (i * 53) as f32 % height as f32;
+        layer.add_sprite(&sheet, [x, y, x + 2.0, y + 2.0], [0.0, 0.0, 1.0, 1.0], 0.0);
+    }
+    graphics.flush().unwrap();
+
+    for _ in 0..WARMUP_FRAMES {
+        graphics.read_pixel(0, 0).unwrap();
+    }
+
+    let times: Vec<Duration> = (0..NFRAMES)
+        .map(|_| {
+            let start = Instant::now();
+            graphics.read_pixel(0, 0).unwrap();
+            start.elapsed()
+        })
+        .collect();
+
+    let average = times.iter().sum::<Duration>() / NFRAMES as u32;
+    let best = times.iter().min().unwrap();
+    println!(
+        "{} instances: {:.3} ms per frame on average, {:.3} ms at best ({} frames)",
+        NINSTANCES,
+        average.as_secs_f64() * 1000.0,
+        best.as_secs_f64() * 1000.0,
+        NFRAMES,
+    );
+}
@@ -0,0 +1,136 @@
+//! Repeatedly fills and empties layers, multi-sheet batches and queued
+//! sprites (including frames with nothing to draw at all), to check that
+//! the renderer copes with empty and rapidly changing scenes.
+//! Press Escape to quit.
+use a2d::Graphics2D;
+use a2d::Instance;
+use a2d::Sheet;
+use futures::executor::block_on;
+use winit::{
+    dpi::LogicalSize,
+    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::WindowBuilder,
+};
+
+const NLAYERS: usize = 16;
+const SPRITES_PER_LAYER: usize = 500;
+
+fn main() {
+    let width = 800;
+    let height = 600;
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_inner_size(LogicalSize { width, height })
+        .build(&event_loop)
+        .unwrap();
+
+    let mut graphics = block_on(Graphics2D::new(width, height, &window)).unwrap();
+    graphics.set_scale([width as f32, height as f32]);
+
+    let sheets = vec![
+        Sheet::from_color(&mut graphics, [1.0, 0.3, 0.3]).unwrap(),
+        Sheet::from_color(&mut graphics, [0.3, 1.0, 0.3]).unwrap(),
+        Sheet::from_color(&mut graphics, [0.3, 0.3, 1.0]).unwrap(),
+    ];
+
+    // Empty sheets are an error rather than a crash
+    assert!(Sheet::from_raw_rgba(&mut graphics, 0, 0, &[]).is_err());
+
+    // Rendering with nothing at all to draw
+    graphics.force_render().unwrap();
+
+    let mut frame = 0usize;
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Poll;
+        match event {
+            Event::MainEventsCleared => {
+                frame += 1;
+                let t = frame as f32;
+
+                // Every few frames, alternate between a full scene and an empty one,
+                // and between recreating everything and reusing what's there
+                let populate = frame % 4 != 0;
+                for i in 0..NLAYERS {
+                    let layer = graphics.add_layer(&format!("layer{}", i));
+                    layer.clear();
+                    layer.set_visible(i % 3 != frame % 3);
+                    if populate {
+                        for j in 0..SPRITES_PER_LAYER {
+                            let x = ((j * 37 + i * 101) as f32 + t) % width as f32;
+                            let y = ((j * 53 + i * 17) as f32 + t * 0.5) % height as f32;
+                            let sheet = &sheets[(i + j) % sheets.len()];
+                            layer.add_sprite(
+                                sheet,
+                                [x, y, x + 4.0, y + 4.0],
+                                [0.0, 0.0, 1.0, 1.0],
+                                0.0,
+                            );
+                        }
+                    }
+                }
+
+                let name = format!("multi{}", frame % 8);
+                if frame % 16 == 0 || graphics.multi_sheet_batch_mut(&name).is_none() {
+                    graphics.add_multi_sheet_batch(&name, &sheets).unwrap();
+                }
+                let multi = graphics.multi_sheet_batch_mut(&name).unwrap();
+                multi.clear();
+                if populate {
+                    for j in 0..SPRITES_PER_LAYER {
+                        let x = (j * 13) as f32 % width as f32;
+                        let y = ((j * 29) as f32 + t) % height as f32;
+                        multi.push(
+                            j % sheets.len(),
+                            Instance::from_rects(
+                                [x, y, x + 6.0, y + 6.0],
+                                [0.0, 0.0, 1.0, 1.0],
+                                t * 0.01,
+                            ),
+                        );
+                    }
+                }
+
+                if frame % 2 == 0 {
+                    for j in 0..1000 {
+                        let x = (j * 7) as f32 % width as f32;
+                        let y = ((j * 11) as f32 + t * 2.0) % height as f32;
+                        graphics.draw_sprite(
+                            &sheets[j % sheets.len()],
+                            [x, y, x + 3.0, y + 3.0],
+                            [0.0, 0.0, 1.0, 1.0],
+                            0.0,
+                        );
+                    }
+                }
+
+                graphics.flush().unwrap();
+                graphics.force_render().unwrap();
+                if frame % 60 == 0 {
+                    println!("frame {}: {:?}", frame, graphics.last_frame_stats());
+                }
+            }
+            Event::WindowEvent {
+                ref event,
+                window_id: _,
+            } => match event {
+                WindowEvent::CloseRequested => {
+                    *control_flow = ControlFlow::Exit;
+                }
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::Escape),
+                            ..
+                        },
+                    ..
+                } => {
+                    *control_flow = ControlFlow::Exit;
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    });
+}
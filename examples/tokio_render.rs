@@ -0,0 +1,33 @@
+//! Drawing with a2d from async code running on tokio.
+//! a2d doesn't spawn tasks or depend on a particular executor, so its
+//! async methods can be awaited from any runtime.
+use a2d::Graphics2D;
+use a2d::Result;
+use winit::{dpi::PhysicalSize, event_loop::EventLoop, window::WindowBuilder};
+
+/// Stands in for e.g. a request handler in a server generating images
+async fn render_swatch(graphics: &mut Graphics2D, color: [f32; 3]) -> Result<[u8; 4]> {
+    graphics.fill_rect([0.0, 0.0, 64.0, 64.0], color)?;
+    graphics.flush_async().await?;
+    graphics.read_pixel(32, 32)
+}
+
+#[tokio::main(basic_scheduler)]
+async fn main() {
+    let width = 64;
+    let height = 64;
+
+    // wgpu needs a window to pick an adapter, but it never has to be shown
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_inner_size(PhysicalSize { width, height })
+        .with_visible(false)
+        .build(&event_loop)
+        .unwrap();
+
+    let mut graphics = Graphics2D::new(width, height, &window).await.unwrap();
+    for &color in &[[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]] {
+        let pixel = render_swatch(&mut graphics, color).await.unwrap();
+        println!("{:?} -> {:?}", color, pixel);
+    }
+}
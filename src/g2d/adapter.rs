@@ -0,0 +1,90 @@
+/// The graphics APIs that a2d looks for adapters on
+/// (see Graphics2D::new and enumerate_adapters)
+pub(super) const BACKENDS: wgpu::BackendBit = wgpu::BackendBit::PRIMARY;
+
+/// A graphics adapter (usually a GPU) that a Graphics2D could be created with
+/// (see enumerate_adapters)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdapterSummary {
+    /// The index to pass to Graphics2D::with_adapter to use this adapter
+    pub index: usize,
+
+    /// A human readable name, e.g. for listing in a settings menu
+    pub name: String,
+
+    pub backend: GraphicsBackend,
+    pub device_type: AdapterType,
+}
+
+/// The graphics API that an adapter is driven through.
+/// The names match those of the corresponding `wgpu::Backend`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphicsBackend {
+    Vulkan,
+    Metal,
+    Dx12,
+    Dx11,
+    Gl,
+    BrowserWebGpu,
+    Other,
+}
+
+impl GraphicsBackend {
+    fn from_wgpu(backend: wgpu::Backend) -> Self {
+        match backend {
+            wgpu::Backend::Vulkan => GraphicsBackend::Vulkan,
+            wgpu::Backend::Metal => GraphicsBackend::Metal,
+            wgpu::Backend::Dx12 => GraphicsBackend::Dx12,
+            wgpu::Backend::Dx11 => GraphicsBackend::Dx11,
+            wgpu::Backend::Gl => GraphicsBackend::Gl,
+            wgpu::Backend::BrowserWebGpu => GraphicsBackend::BrowserWebGpu,
+            wgpu::Backend::Empty => GraphicsBackend::Other,
+        }
+    }
+}
+
+/// The kind of device an adapter is.
+/// The names match those of the corresponding `wgpu::DeviceType`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdapterType {
+    IntegratedGpu,
+    DiscreteGpu,
+    VirtualGpu,
+
+    /// A software renderer (see Graphics2D::is_hardware_accelerated)
+    Cpu,
+
+    Other,
+}
+
+impl AdapterType {
+    fn from_wgpu(device_type: wgpu::DeviceType) -> Self {
+        match device_type {
+            wgpu::DeviceType::IntegratedGpu => AdapterType::IntegratedGpu,
+            wgpu::DeviceType::DiscreteGpu => AdapterType::DiscreteGpu,
+            wgpu::DeviceType::VirtualGpu => AdapterType::VirtualGpu,
+            wgpu::DeviceType::Cpu => AdapterType::Cpu,
+            wgpu::DeviceType::Other => AdapterType::Other,
+        }
+    }
+}
+
+/// Lists the adapters available on this machine (across Vulkan, Metal and DX12),
+/// e.g. to let users pick a GPU before calling Graphics2D::with_adapter.
+///
+/// Not every adapter listed is necessarily able to draw to a given window
+pub fn enumerate_adapters() -> Vec<AdapterSummary> {
+    wgpu::Adapter::enumerate(BACKENDS)
+        .iter()
+        .enumerate()
+        .map(|(index, adapter)| {
+            let info = adapter.get_info();
+            AdapterSummary {
+                index,
+                name: info.name,
+                backend: GraphicsBackend::from_wgpu(info.backend),
+                device_type: AdapterType::from_wgpu(info.device_type),
+            }
+        })
+        .collect()
+}
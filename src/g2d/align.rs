@@ -0,0 +1,45 @@
+//! Helpers for copying between buffers and textures.
+//! wgpu requires `bytes_per_row` in these copies to be a multiple of
+//! COPY_BYTES_PER_ROW_ALIGNMENT, so rows of pixels have to be padded
+//! when going to the GPU (and unpadded when coming back).
+//! All of these assume 4 bytes per pixel (RGBA8 or BGRA8)
+
+pub(super) const COPY_BYTES_PER_ROW_ALIGNMENT: u32 = 256;
+
+const BYTES_PER_PIXEL: u32 = 4;
+
+/// Returns the smallest valid `bytes_per_row` for a copy
+/// of an image `width` pixels wide
+// u32::div_ceil is newer than the Rust versions this crate supports
+#[allow(clippy::manual_div_ceil)]
+pub(super) fn padded_bytes_per_row(width: u32) -> u32 {
+    let unpadded = BYTES_PER_PIXEL * width;
+    let align = COPY_BYTES_PER_ROW_ALIGNMENT;
+    (unpadded + align - 1) / align * align
+}
+
+/// Copies tightly packed rows of pixels into a buffer where
+/// each row is `padded_bytes_per_row(width)` bytes long
+pub(super) fn pad_rows(bytes: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let unpadded = (BYTES_PER_PIXEL * width) as usize;
+    let padded = padded_bytes_per_row(width) as usize;
+    if unpadded == padded {
+        return bytes.to_vec();
+    }
+    let mut out = vec![0; padded * height as usize];
+    for (src, dst) in bytes.chunks(unpadded).zip(out.chunks_mut(padded)) {
+        dst[..unpadded].copy_from_slice(src);
+    }
+    out
+}
+
+/// The inverse of `pad_rows`
+pub(super) fn unpad_rows(bytes: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let unpadded = (BYTES_PER_PIXEL * width) as usize;
+    let padded = padded_bytes_per_row(width) as usize;
+    let mut out = Vec::with_capacity(unpadded * height as usize);
+    for row in bytes.chunks(padded).take(height as usize) {
+        out.extend_from_slice(&row[..unpadded]);
+    }
+    out
+}
@@ -1,11 +1,80 @@
 use super::*;
-use std::rc::Rc;
+
+/// Data for the per-batch uniform (TranslationUniform in shader.vert)
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub(super) struct BatchUniform {
+    pub scale: Scaling,
+    pub translation: Translation,
+    pub tint: [f32; 4],
+    pub rotation_origin: [f32; 2],
+    pub rotation: f32,
+
+    /// std140 aligns color_override to 16 bytes
+    pub _padding: f32,
+
+    /// If the alpha is non-zero, sprites are drawn in this color instead of
+    /// their own (keeping only the alpha of the sheet), e.g. for shadows
+    pub color_override: [f32; 4],
+}
+
+unsafe impl bytemuck::Pod for BatchUniform {}
+unsafe impl bytemuck::Zeroable for BatchUniform {}
+
+impl BatchUniform {
+    /// Leaves sprites unchanged
+    pub const IDENTITY: BatchUniform = BatchUniform {
+        scale: [1.0, 1.0],
+        translation: [0.0, 0.0],
+        tint: [1.0, 1.0, 1.0, 1.0],
+        rotation_origin: [0.0, 0.0],
+        rotation: 0.0,
+        _padding: 0.0,
+        color_override: [0.0, 0.0, 0.0, 0.0],
+    };
+}
 
 pub(super) struct Batch {
-    sheet: Rc<Sheet>,
+    sheet: Arc<Sheet>,
     instance_buffer: wgpu::Buffer,
     scale: Scaling,
     translation: Translation,
+
+    /// Additional translations to draw this batch with.
+    /// If non-empty, the batch is drawn once per entry (each applied
+    /// on top of `translation`) instead of just once.
+    transforms: Vec<Translation>,
+
+    /// Extra copies of this batch drawn beneath it, each offset by the
+    /// given translation and drawn entirely in the given color
+    /// (used for text shadows and outlines)
+    underlays: Vec<(Translation, Color)>,
+
+    /// Color multiplied with every sprite in this batch
+    tint: Color,
+
+    /// Clockwise rotation in radians of the entire batch around rotation_origin,
+    /// applied after scale and translation
+    rotation: f32,
+    rotation_origin: [f32; 2],
+
+    /// How the sheet is sampled when drawing this batch
+    filter: Filter,
+
+    /// If set, the red channel of the sheet is used as an index into this
+    /// sheet's first row of pixels for the color to draw
+    palette: Option<Arc<Sheet>>,
+
+    /// Name shown for this batch's GPU objects in debugging tools
+    label: Option<String>,
+
+    /// Applied on top of the batch's own transformation for the next render only
+    frame_transform: Option<Transform2D>,
+
+    /// If set, only the instances in this range are drawn
+    /// (clamped to the length of the batch)
+    draw_range: Option<Range<usize>>,
+
     nrows: usize,
     ncols: usize,
     len: usize,
@@ -17,7 +86,7 @@ pub(super) struct Batch {
 impl Batch {
     pub fn new(
         graphics: &mut Graphics2D,
-        sheet: Rc<Sheet>,
+        sheet: Arc<Sheet>,
         nrows: usize,
         ncols: usize,
         descs: &[SpriteDesc],
@@ -35,7 +104,8 @@ impl Batch {
             );
         }
 
-        let instance_buffer = graphics.device.create_buffer_with_data(
+        let instance_buffer = imp::create_buffer_checked(
+            &graphics.device,
             bytemuck::cast_slice(&instances),
             wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::MAP_WRITE,
         );
@@ -45,6 +115,16 @@ impl Batch {
             instance_buffer,
             scale: [1.0, 1.0],
             translation: [0.0, 0.0],
+            transforms: vec![],
+            underlays: vec![],
+            tint: [1.0, 1.0, 1.0, 1.0].into(),
+            rotation: 0.0,
+            rotation_origin: [0.0, 0.0],
+            filter: Filter::default(),
+            palette: None,
+            label: None,
+            frame_transform: None,
+            draw_range: None,
             nrows,
             ncols,
             len: instances.len(),
@@ -56,6 +136,18 @@ impl Batch {
         &self.sheet
     }
 
+    pub fn set_sheet(&mut self, sheet: Arc<Sheet>) {
+        self.sheet = sheet;
+    }
+
+    pub fn palette(&self) -> Option<&Sheet> {
+        self.palette.as_deref()
+    }
+
+    pub fn set_palette(&mut self, palette: Option<Arc<Sheet>>) {
+        self.palette = palette;
+    }
+
     /// The scaling that's applied before performing the batch translation
     /// This allows scaling the size of all elements in a batch at once
     /// independent of all other batches
@@ -75,7 +167,124 @@ impl Batch {
         self.translation = translation;
     }
 
+    pub fn tint(&self) -> Color {
+        self.tint
+    }
+
+    pub fn set_tint(&mut self, tint: Color) {
+        self.tint = tint;
+    }
+
+    pub fn rotation(&self) -> f32 {
+        self.rotation
+    }
+
+    pub fn set_rotation(&mut self, rotation: f32) {
+        self.rotation = rotation;
+    }
+
+    pub fn rotation_origin(&self) -> [f32; 2] {
+        self.rotation_origin
+    }
+
+    pub fn set_rotation_origin(&mut self, rotation_origin: [f32; 2]) {
+        self.rotation_origin = rotation_origin;
+    }
+
+    pub fn filter(&self) -> Filter {
+        self.filter
+    }
+
+    pub fn set_filter(&mut self, filter: Filter) {
+        self.filter = filter;
+    }
+
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    pub fn set_label(&mut self, label: &str) {
+        self.label = Some(label.to_owned());
+    }
+
+    pub fn set_frame_transform(&mut self, transform: Transform2D) {
+        self.frame_transform = Some(transform);
+    }
+
+    pub fn clear_frame_transform(&mut self) {
+        self.frame_transform = None;
+    }
+
+    /// The uniforms to actually draw this batch with, one draw per entry
+    /// (see draw_translations). Includes the frame transform, if any
+    pub fn draw_uniforms(&self) -> Vec<BatchUniform> {
+        let frame = self.frame_transform.unwrap_or_default();
+        let [sx, sy] = self.scale;
+        let [dx, dy] = frame.translation;
+        let uniforms: Vec<_> = self
+            .draw_translations()
+            .into_iter()
+            .map(|[x, y]| BatchUniform {
+                scale: [sx * frame.scale[0], sy * frame.scale[1]],
+                translation: [x + dx, y + dy],
+                tint: self.tint.to_array(),
+                rotation_origin: self.rotation_origin,
+                rotation: self.rotation + frame.rotation,
+                _padding: 0.0,
+                color_override: [0.0, 0.0, 0.0, 0.0],
+            })
+            .collect();
+
+        // Underlays are drawn first, so that they end up beneath the batch itself
+        let mut all = vec![];
+        for ([ox, oy], color) in &self.underlays {
+            for uniform in &uniforms {
+                let [x, y] = uniform.translation;
+                all.push(BatchUniform {
+                    translation: [x + ox, y + oy],
+                    color_override: color.to_array(),
+                    ..*uniform
+                });
+            }
+        }
+        all.extend(uniforms);
+        all
+    }
+
+    pub fn set_underlays(&mut self, underlays: Vec<(Translation, Color)>) {
+        self.underlays = underlays;
+    }
+
+    pub fn transforms(&self) -> &[Translation] {
+        &self.transforms
+    }
+
+    pub fn set_transforms(&mut self, transforms: Vec<Translation>) {
+        self.transforms = transforms;
+    }
+
+    /// The translations to actually draw this batch with, one draw per entry
+    pub fn draw_translations(&self) -> Vec<Translation> {
+        if self.transforms.is_empty() {
+            vec![self.translation]
+        } else {
+            let [x, y] = self.translation;
+            self.transforms
+                .iter()
+                .map(|[dx, dy]| [x + dx, y + dy])
+                .collect()
+        }
+    }
+
+    /// Panics if i is out of bounds (rather than failing later in flush,
+    /// when the update would be written outside of the instance buffer)
     pub fn get(&mut self, i: usize) -> SpriteView {
+        assert!(
+            i < self.len,
+            "Sprite index {} out of bounds for a batch with {} sprites",
+            i,
+            self.len,
+        );
         SpriteView { batch: self, i }
     }
 
@@ -83,6 +292,37 @@ impl Batch {
         self.len
     }
 
+    pub fn draw_range(&self) -> Option<Range<usize>> {
+        self.draw_range.clone()
+    }
+
+    pub fn set_draw_range(&mut self, range: Option<Range<usize>>) {
+        self.draw_range = range;
+    }
+
+    /// The instances that will actually be drawn, i.e. the draw range
+    /// clamped to the batch (or the whole batch if there's no draw range)
+    pub fn drawn_instances(&self) -> Range<usize> {
+        match &self.draw_range {
+            Some(range) => {
+                let end = range.end.min(self.len);
+                range.start.min(end)..end
+            }
+            None => 0..self.len,
+        }
+    }
+
+    /// True if there are changes that haven't been written to the
+    /// instance buffer yet (i.e. the next flush has something to do)
+    pub fn is_dirty(&self) -> bool {
+        !self.pending_updates.is_empty()
+    }
+
+    /// Discards all changes that haven't been written to the instance buffer yet
+    pub fn mark_clean(&mut self) {
+        self.pending_updates.clear();
+    }
+
     pub fn instance_buffer(&self) -> &wgpu::Buffer {
         &self.instance_buffer
     }
@@ -0,0 +1,108 @@
+use super::*;
+
+/// Settings for creating a Graphics2D, for when there are more than
+/// Graphics2D::new covers (see Graphics2D::builder).
+///
+/// Everything left unset keeps the same default as Graphics2D::new,
+/// and can still be changed with the corresponding `set_*` method later
+pub struct Graphics2DBuilder {
+    adapter_index: Option<usize>,
+    scale: Option<[f32; 2]>,
+    y_axis: YAxis,
+    orientation: Orientation,
+    depth: bool,
+    clear_on_render: bool,
+    pixel_snapping: bool,
+    target_fps: Option<f32>,
+    render_when_hidden: bool,
+    max_instances_per_batch: Option<usize>,
+}
+
+impl Graphics2DBuilder {
+    pub(super) fn new() -> Self {
+        Self {
+            adapter_index: None,
+            scale: None,
+            y_axis: YAxis::Down,
+            orientation: Orientation::Normal,
+            depth: false,
+            clear_on_render: true,
+            pixel_snapping: false,
+            target_fps: None,
+            render_when_hidden: true,
+            max_instances_per_batch: None,
+        }
+    }
+
+    /// Creates a Graphics2D drawing to the given window with these settings
+    pub async fn build<W: HasRawWindowHandle>(
+        self,
+        width: u32,
+        height: u32,
+        window: &W,
+    ) -> Result<Graphics2D> {
+        let mut graphics = Graphics2D::new0(width, height, window, self.adapter_index).await?;
+        graphics.set_scale(self.scale.unwrap_or([width as f32, height as f32]));
+        graphics.set_y_axis(self.y_axis);
+        graphics.set_orientation(self.orientation);
+        graphics.enable_depth(self.depth);
+        graphics.set_clear_on_render(self.clear_on_render);
+        graphics.set_pixel_snapping(self.pixel_snapping);
+        graphics.set_target_fps(self.target_fps);
+        graphics.set_render_when_hidden(self.render_when_hidden);
+        graphics.set_max_instances_per_batch(self.max_instances_per_batch);
+        Ok(graphics)
+    }
+
+    /// See Graphics2D::with_adapter
+    pub fn adapter(mut self, adapter_index: usize) -> Self {
+        self.adapter_index = Some(adapter_index);
+        self
+    }
+
+    /// Defaults to the size of the window
+    pub fn scale(mut self, scale: [f32; 2]) -> Self {
+        self.scale = Some(scale);
+        self
+    }
+
+    pub fn y_axis(mut self, y_axis: YAxis) -> Self {
+        self.y_axis = y_axis;
+        self
+    }
+
+    pub fn orientation(mut self, orientation: Orientation) -> Self {
+        self.orientation = orientation;
+        self
+    }
+
+    pub fn depth(mut self, depth: bool) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    pub fn clear_on_render(mut self, clear_on_render: bool) -> Self {
+        self.clear_on_render = clear_on_render;
+        self
+    }
+
+    pub fn pixel_snapping(mut self, pixel_snapping: bool) -> Self {
+        self.pixel_snapping = pixel_snapping;
+        self
+    }
+
+    pub fn target_fps(mut self, target_fps: Option<f32>) -> Self {
+        self.target_fps = target_fps;
+        self
+    }
+
+    pub fn render_when_hidden(mut self, render_when_hidden: bool) -> Self {
+        self.render_when_hidden = render_when_hidden;
+        self
+    }
+
+    pub fn max_instances_per_batch(mut self, max: Option<usize>) -> Self {
+        self.max_instances_per_batch = max;
+        self
+    }
+}
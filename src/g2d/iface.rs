@@ -2,19 +2,113 @@ use super::*;
 
 /// Public methods of Graphics2D
 impl Graphics2D {
+    /// Creates a Graphics2D drawing to the given window.
+    ///
+    /// Like all async methods in a2d, this doesn't spawn any tasks or rely on
+    /// any particular executor, so it can be driven with
+    /// `futures::executor::block_on` or awaited from any async runtime (e.g. tokio)
     pub async fn new<W: HasRawWindowHandle>(width: u32, height: u32, window: &W) -> Result<Self> {
-        let mut graphics = Self::new0(width, height, window).await?;
+        let mut graphics = Self::new0(width, height, window, None).await?;
+        graphics.set_scale([width as f32, height as f32]);
+        Ok(graphics)
+    }
+
+    /// For creating a Graphics2D with settings other than the defaults,
+    /// e.g. `Graphics2D::builder().y_axis(YAxis::Up).build(width, height, &window)`
+    pub fn builder() -> Graphics2DBuilder {
+        Graphics2DBuilder::new()
+    }
+
+    /// Like Graphics2D::new, but uses the given adapter (the `index` of one
+    /// of the AdapterSummaries returned by enumerate_adapters) instead of
+    /// letting wgpu choose one
+    pub async fn with_adapter<W: HasRawWindowHandle>(
+        width: u32,
+        height: u32,
+        window: &W,
+        adapter_index: usize,
+    ) -> Result<Self> {
+        let mut graphics = Self::new0(width, height, window, Some(adapter_index)).await?;
         graphics.set_scale([width as f32, height as f32]);
         Ok(graphics)
     }
 
+    /// Returns false if wgpu fell back to a software renderer
+    /// (e.g. llvmpipe on CI machines and some VMs), in which case drawing
+    /// may be very slow and it may be worth switching to a reduced-quality mode
+    pub fn is_hardware_accelerated(&self) -> bool {
+        match self.adapter_info.device_type {
+            wgpu::DeviceType::Cpu | wgpu::DeviceType::Other => false,
+            wgpu::DeviceType::IntegratedGpu
+            | wgpu::DeviceType::DiscreteGpu
+            | wgpu::DeviceType::VirtualGpu => true,
+        }
+    }
+
+    /// The largest width or height a Sheet may have on this device.
+    /// Creating a larger sheet returns an error
+    ///
+    /// wgpu 0.5 doesn't report texture size limits, so this is always
+    /// MAX_TEXTURE_DIMENSION, the minimum every backend is expected to support
+    pub fn max_texture_dimension(&self) -> u32 {
+        MAX_TEXTURE_DIMENSION
+    }
+
+    /// The color format of the window's swap chain, e.g. for creating
+    /// pipelines that render into the same frame as a2d
+    pub fn surface_format(&self) -> SurfaceFormat {
+        SurfaceFormat::from_wgpu(self.sc_desc.format)
+    }
+
     /// Flushes all pending writes to their buffers
     /// This needs to be called for render to show updates
     pub fn flush(&mut self) -> Result<()> {
-        self.ensure_polling()?;
-        futures::executor::block_on(self.async_flush())?;
-        self.dirty = true;
-        Ok(())
+        self.reporting(|graphics| {
+            graphics.ensure_polling()?;
+            futures::executor::block_on(graphics.async_flush())?;
+            graphics.dirty = true;
+            Ok(())
+        })
+    }
+
+    /// Like `flush`, but can be awaited instead of blocking the current thread
+    /// (e.g. in an async request handler).
+    ///
+    /// The GPU is driven by a2d's own background thread rather than by the
+    /// executor, so this works with any async runtime
+    pub async fn flush_async(&mut self) -> Result<()> {
+        let result = async {
+            self.ensure_polling()?;
+            self.async_flush().await?;
+            self.dirty = true;
+            Ok(())
+        }
+        .await;
+        self.report(result)
+    }
+
+    /// Sets a callback that is called with a description of every error
+    /// returned from flushing or rendering, in addition to the error being
+    /// returned. This gives applications a single place to capture
+    /// (e.g. log) GPU related errors.
+    ///
+    /// The handler has to be Send, so that the Graphics2D stays Send.
+    pub fn set_error_handler<F: Fn(&str) + Send + 'static>(&mut self, handler: F) {
+        self.error_handler = Some(Box::new(handler));
+    }
+
+    /// Drives the device, completing any finished GPU work
+    /// (e.g. callbacks for buffer mappings).
+    /// If `wait` is true, blocks until all submitted work is complete.
+    ///
+    /// `flush` already takes care of this with a background thread,
+    /// so this is only needed when driving the device manually.
+    pub fn poll(&self, wait: bool) {
+        self.device.poll(if wait {
+            wgpu::Maintain::Wait
+        } else {
+            wgpu::Maintain::Poll
+        });
     }
 
     pub fn render_if_dirty(&mut self) -> Result<()> {
@@ -24,107 +118,431 @@ impl Graphics2D {
         Ok(())
     }
 
+    /// Draws everything to the window, even if nothing changed
+    /// since the last render (see `render_if_dirty`).
+    ///
+    /// If there's nothing to draw and `clear_on_render` is off, the window
+    /// would be left as is anyway, so this returns right away without
+    /// touching the GPU.
+    ///
+    /// While the window is hidden and `render_when_hidden` is off, this
+    /// also returns right away, but the Graphics2D stays dirty so that
+    /// `render_if_dirty` draws again once the window is shown.
     pub fn force_render(&mut self) -> Result<()> {
-        self.dirty = false;
-        struct BatchInfo<'a> {
-            batch: &'a Batch,
-            instance_buffer: &'a wgpu::Buffer,
-            translation_bind_group: wgpu::BindGroup,
-            instance_len: usize,
-        }
-        let batches_with_instance_buffers = {
-            let mut vec = Vec::new();
-            for batch in self.batches.iter().rev().flatten() {
-                let instance_buffer = batch.instance_buffer();
-                let instance_len = batch.len();
-                let translation_buffer = self.device.create_buffer_with_data(
-                    bytemuck::cast_slice(&[batch.scale(), batch.translation()]),
-                    wgpu::BufferUsage::UNIFORM,
+        if self.hidden && !self.render_when_hidden {
+            self.skip_hidden_frame();
+            return Ok(());
+        }
+        if self.can_skip_render() {
+            self.dirty = false;
+            self.last_frame_stats = RenderStats::default();
+            return Ok(());
+        }
+        self.pace_frame();
+        self.reporting(|graphics| {
+            let frame = match graphics.swap_chain.get_next_texture() {
+                Ok(frame) => frame,
+                Err(wgpu::TimeOut) => err!("Timeout getting next texture"),
+            };
+            let [width, height] = [graphics.sc_desc.width, graphics.sc_desc.height];
+            graphics.render_to_view(&frame.view, width, height)
+        })
+    }
+
+    /// Flushes all pending updates, renders, and then blocks until the GPU
+    /// has finished all submitted work (e.g. for simple programs and tests
+    /// that just want a single frame on screen, or something to read back)
+    pub fn render_and_flush(&mut self) -> Result<()> {
+        self.flush()?;
+        self.force_render()?;
+        self.device.poll(wgpu::Maintain::Wait);
+        Ok(())
+    }
+
+    /// Renders everything to an offscreen target the size of the window and
+    /// returns the [r, g, b, a] color of the pixel at physical coordinates [x, y]
+    /// (e.g. for tests and color pickers).
+    ///
+    /// This blocks until the GPU is done. Like `force_render`, it also uses up
+    /// the sprites queued with `draw_sprite`.
+    pub fn read_pixel(&mut self, x: u32, y: u32) -> Result<[u8; 4]> {
+        self.reporting(|graphics| {
+            let [width, height] = [graphics.sc_desc.width, graphics.sc_desc.height];
+            if x >= width || y >= height {
+                err!(
+                    "Pixel [{}, {}] is outside of the {}x{} window",
+                    x,
+                    y,
+                    width,
+                    height
                 );
-                let translation_bind_group =
-                    self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-                        layout: &self.translation_uniform_bind_group_layout,
-                        bindings: &[wgpu::Binding {
-                            binding: 0,
-                            resource: wgpu::BindingResource::Buffer {
-                                buffer: &translation_buffer,
-                                range: 0..(std::mem::size_of::<Scaling>()
-                                    + std::mem::size_of::<Translation>())
-                                    as wgpu::BufferAddress,
-                            },
-                        }],
-                        label: Some("per_batch_scale_uniform_bind_group"),
-                    });
-                vec.push(BatchInfo {
-                    batch,
-                    instance_buffer,
-                    translation_bind_group,
-                    instance_len,
-                });
             }
-            vec
-        };
-        let scale_uniform_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &self.scale_uniform_bind_group_layout,
-            bindings: &[wgpu::Binding {
-                binding: 0,
-                resource: wgpu::BindingResource::Buffer {
-                    buffer: &self.scale_uniform_buffer,
-                    range: 0..std::mem::size_of::<Scaling>() as wgpu::BufferAddress,
+            // Every pipeline is single-sampled, so the target is too. If
+            // multisampling is ever added, this has to render to a multisampled
+            // texture instead, with this one as its resolve_target, since
+            // multisampled textures can't be copied to a buffer directly.
+            let texture = graphics.device.create_texture(&wgpu::TextureDescriptor {
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth: 1,
                 },
-            }],
-            label: Some("default_scale_uniform_bind_group"),
-        });
-        let frame = self
-            .swap_chain
-            .get_next_texture()
-            .expect("Timeout getting next texture");
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render Encoder"),
+                array_layer_count: 1,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: graphics.sc_desc.format,
+                usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::COPY_SRC,
+                label: Some("read_pixel_texture"),
             });
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
-                    attachment: &frame.view,
-                    resolve_target: None,
-                    load_op: wgpu::LoadOp::Clear,
-                    store_op: wgpu::StoreOp::Store,
-                    clear_color: wgpu::Color {
-                        r: 0.0,
-                        g: 0.0,
-                        b: 0.0,
-                        a: 0.0,
-                    },
-                }],
-                depth_stencil_attachment: None,
+            graphics.render_to_view(&texture.create_default_view(), width, height)?;
+
+            // Even a single pixel has to be copied with an aligned row size
+            let bytes_per_row = padded_bytes_per_row(1);
+            let buffer = graphics.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("read_pixel_buffer"),
+                size: bytes_per_row as wgpu::BufferAddress,
+                usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
             });
-            render_pass.set_pipeline(&self.render_pipeline);
-            for info in &batches_with_instance_buffers {
-                let batch = info.batch;
-                let instance_buffer = &info.instance_buffer;
-                let translation_bind_group = &info.translation_bind_group;
-                let instance_len = info.instance_len;
-                render_pass.set_bind_group(0, batch.sheet().bind_group(), &[]);
-                render_pass.set_bind_group(1, &scale_uniform_bind_group, &[]);
-                render_pass.set_bind_group(2, translation_bind_group, &[]);
-                render_pass.set_vertex_buffer(0, instance_buffer, 0, 0);
-                render_pass.draw(0..6, 0..instance_len as u32);
-            }
-        }
+            let mut encoder =
+                graphics
+                    .device
+                    .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                        label: Some("read_pixel_encoder"),
+                    });
+            encoder.copy_texture_to_buffer(
+                wgpu::TextureCopyView {
+                    texture: &texture,
+                    mip_level: 0,
+                    array_layer: 0,
+                    origin: wgpu::Origin3d { x, y, z: 0 },
+                },
+                wgpu::BufferCopyView {
+                    buffer: &buffer,
+                    offset: 0,
+                    bytes_per_row,
+                    rows_per_image: 1,
+                },
+                wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth: 1,
+                },
+            );
+            graphics.queue.submit(&[encoder.finish()]);
 
-        self.queue.submit(&[encoder.finish()]);
-        Ok(())
+            let mapping = buffer.map_read(0, bytes_per_row as wgpu::BufferAddress);
+            graphics.device.poll(wgpu::Maintain::Wait);
+            let mapping = futures::executor::block_on(mapping)?;
+            let pixel = unpad_rows(mapping.as_slice(), 1, 1);
+            Ok(match graphics.sc_desc.format {
+                wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => {
+                    [pixel[2], pixel[1], pixel[0], pixel[3]]
+                }
+                _ => [pixel[0], pixel[1], pixel[2], pixel[3]],
+            })
+        })
+    }
+
+    /// Adds another window to draw to, sharing all sheets and batches with
+    /// the main window (e.g. for a detached preview window).
+    ///
+    /// `width` and `height` are the physical size of the window.
+    /// Use `render_to_surface` to draw to it.
+    pub fn add_surface<W: HasRawWindowHandle>(
+        &mut self,
+        window: &W,
+        width: u32,
+        height: u32,
+    ) -> SurfaceId {
+        let surface = wgpu::Surface::create(window);
+        let sc_desc = wgpu::SwapChainDescriptor {
+            width,
+            height,
+            ..self.sc_desc.clone()
+        };
+        let swap_chain = self.device.create_swap_chain(&surface, &sc_desc);
+        self.surfaces.push(ExtraSurface {
+            surface,
+            sc_desc,
+            swap_chain,
+        });
+        SurfaceId(self.surfaces.len() - 1)
+    }
+
+    /// Call this method to notify A2D that a window added with
+    /// `add_surface` has been resized
+    pub fn resized_surface(&mut self, id: SurfaceId, width: u32, height: u32) {
+        let extra = &mut self.surfaces[id.0];
+        extra.sc_desc.width = width;
+        extra.sc_desc.height = height;
+        extra.swap_chain = self
+            .device
+            .create_swap_chain(&extra.surface, &extra.sc_desc);
+    }
+
+    /// Like `force_render`, but draws to a window added with `add_surface`.
+    ///
+    /// Note that sprites queued with `draw_sprite` are cleared by whichever
+    /// render happens first, so they need to be queued again before
+    /// drawing them to another window.
+    pub fn render_to_surface(&mut self, id: SurfaceId) -> Result<()> {
+        self.reporting(|graphics| {
+            let frame = match graphics.surfaces[id.0].swap_chain.get_next_texture() {
+                Ok(frame) => frame,
+                Err(wgpu::TimeOut) => err!("Timeout getting next texture"),
+            };
+            let sc_desc = &graphics.surfaces[id.0].sc_desc;
+            let [width, height] = [sc_desc.width, sc_desc.height];
+            graphics.render_to_view(&frame.view, width, height)
+        })
+    }
+
+    /// Statistics about the most recent call to `force_render`
+    /// (or `render_if_dirty`, if it actually rendered)
+    ///
+    /// These don't include how long the GPU spent on the frame, since that
+    /// needs timestamp queries, which wgpu 0.5 doesn't have
+    pub fn last_frame_stats(&self) -> RenderStats {
+        self.last_frame_stats
+    }
+
+    pub fn max_instances_per_batch(&self) -> Option<usize> {
+        self.max_instances_per_batch
+    }
+
+    /// Sets a limit on the number of instances drawn with a single draw call
+    /// (i.e. per batch, or per group of consecutive sprites from the same sheet).
+    /// When set, rendering returns an error if anything exceeds the limit,
+    /// instead of failing inside wgpu when allocating huge buffers.
+    ///
+    /// There is no limit by default
+    pub fn set_max_instances_per_batch(&mut self, max: Option<usize>) {
+        self.max_instances_per_batch = max;
     }
 
     /// Call this method to notify A2D that the window has been resized
+    ///
+    /// This also resets the scale to [width, height], so that
+    /// 1 unit is 1 pixel. Call `set_scale` afterwards to use
+    /// other coordinates (e.g. logical pixels on high-DPI displays).
     pub fn resized(&mut self, width: u32, height: u32) {
         self.sc_desc.width = width;
         self.sc_desc.height = height;
         self.swap_chain = self.device.create_swap_chain(&self.surface, &self.sc_desc);
         self.set_scale([width as f32, height as f32]);
         self.text_grid_dim = None;
+        if self.depth_enabled {
+            self.ensure_depth_texture(width, height);
+        }
+    }
+
+    pub fn target_fps(&self) -> Option<f32> {
+        self.target_fps
+    }
+
+    /// Limits how often `force_render` (and so `render_if_dirty`) draws,
+    /// by sleeping until at least 1 / fps seconds have passed since the
+    /// start of the previous frame. This saves power in apps that don't need
+    /// high frame rates, independently of vsync. `None` (the default)
+    /// means no limit.
+    ///
+    /// This relies on `std::thread::sleep`, which may oversleep by a
+    /// millisecond or more depending on the OS, so the actual frame rate
+    /// may be a little below the target.
+    pub fn set_target_fps(&mut self, fps: Option<f32>) {
+        self.target_fps = fps.filter(|fps| *fps > 0.0);
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.hidden
+    }
+
+    /// Tells a2d whether the window is currently hidden, e.g. minimized or
+    /// in the background. a2d can't tell on its own, so this has to be fed in
+    /// from the event loop. With winit, call this with `!focused` on
+    /// `WindowEvent::Focused(focused)`, and with whether the new size is 0x0
+    /// on `WindowEvent::Resized` (which is how minimizing shows up on Windows).
+    ///
+    /// This has no effect unless `set_render_when_hidden(false)` is called
+    pub fn set_hidden(&mut self, hidden: bool) {
+        self.hidden = hidden;
+    }
+
+    pub fn render_when_hidden(&self) -> bool {
+        self.render_when_hidden
+    }
+
+    /// If false, `force_render` (and so `render_if_dirty`) does nothing while
+    /// the window is hidden (see `set_hidden`), to save power in the background.
+    /// Sprites queued for those frames are dropped as if they had been drawn,
+    /// and the swap chain is left as is, so rendering resumes normally once
+    /// the window is shown again.
+    /// Combined with `set_target_fps`, this lets background apps stay nearly idle.
+    ///
+    /// On by default
+    pub fn set_render_when_hidden(&mut self, render_when_hidden: bool) {
+        self.render_when_hidden = render_when_hidden;
+    }
+
+    /// Calls `f` once for every `dt` of time that has passed since the previous
+    /// call to step_fixed, carrying over whatever is left to the next call,
+    /// and returns how many times it was called.
+    /// Call this once per frame to run game logic (e.g. physics) at a fixed rate,
+    /// independently of how often frames are rendered.
+    ///
+    /// The first call only starts the clock, and so never calls `f`.
+    /// At most MAX_FIXED_STEPS_PER_CALL steps are run per call; any more
+    /// time than that is dropped, so after a long stall the game slows down
+    /// briefly instead of trying to catch up all at once.
+    /// Panics if dt is zero
+    pub fn step_fixed<F: FnMut()>(&mut self, dt: Duration, mut f: F) -> usize {
+        assert!(
+            dt > Duration::from_secs(0),
+            "step_fixed needs a non-zero dt"
+        );
+        let now = Instant::now();
+        if let Some(last) = self.last_fixed_step_time {
+            self.fixed_step_accumulator += now - last;
+        }
+        self.last_fixed_step_time = Some(now);
+        let mut steps = 0;
+        while self.fixed_step_accumulator >= dt {
+            if steps == MAX_FIXED_STEPS_PER_CALL {
+                self.fixed_step_accumulator = Duration::from_secs(0);
+                break;
+            }
+            f();
+            self.fixed_step_accumulator -= dt;
+            steps += 1;
+        }
+        steps
+    }
+
+    pub fn viewport(&self) -> Option<Rect> {
+        self.viewport
+    }
+
+    /// Confines drawing to the given part of the window (in the coordinates
+    /// set by `set_scale`), e.g. for split-screen or a thumbnail in a corner.
+    /// `None` (the default) draws to the whole window.
+    ///
+    /// Note that a viewport *transforms* rather than clips: the entire scene
+    /// (everything that would have covered the whole window) is squeezed
+    /// into the viewport. To cut off whatever falls outside of a region
+    /// without resizing anything, a scissor rect is needed instead.
+    ///
+    /// The viewport must lie within the window
+    pub fn set_viewport(&mut self, viewport: Option<Rect>) {
+        self.viewport = viewport;
+        self.dirty = true;
+    }
+
+    pub fn clear_on_render(&self) -> bool {
+        self.clear_on_render
+    }
+
+    /// Sets whether each render starts by clearing the window (true by default).
+    ///
+    /// When false, everything is drawn over what was already there
+    /// (e.g. for motion trails). Note that the window's swap chain has more
+    /// than one buffer, so what's already there is whatever was last drawn
+    /// to that particular buffer, which is usually *not* the previous frame.
+    /// The contents of a buffer that hasn't been drawn to yet are undefined.
+    pub fn set_clear_on_render(&mut self, clear_on_render: bool) {
+        self.clear_on_render = clear_on_render;
+    }
+
+    /// Replaces the vertex and fragment shaders (e.g. while iterating on a
+    /// shader, without restarting). Both are compiled SPIR-V, and must have
+    /// the same inputs, outputs and bindings as the builtin shaders in `shaders`.
+    ///
+    /// Returns an error if either isn't valid SPIR-V. Note that wgpu doesn't
+    /// validate the contents of shader modules, so shaders with a mismatched
+    /// interface may still fail later (e.g. when rendering).
+    /// Pass `shaders::VERT` and `shaders::FRAG` to go back to the defaults.
+    pub fn reload_shaders(&mut self, vert_spirv: &[u8], frag_spirv: &[u8]) -> Result<()> {
+        let vs = create_shader_module(&self.device, vert_spirv, "vertex shader")?;
+        let fs = create_shader_module(&self.device, frag_spirv, "fragment shader")?;
+        // MultiSheetBatches share the vertex shader, but keep their own
+        // fragment shader, since it has different bindings
+        self.shader_modules.vs = vs;
+        self.shader_modules.fs = fs;
+        self.rebuild_pipelines();
+        Ok(())
+    }
+
+    pub fn debug_mode(&self) -> DebugMode {
+        self.debug_mode
+    }
+
+    /// Switches to an alternate fragment shader that helps track down
+    /// rendering problems (`DebugMode::Normal` by default, which draws as usual).
+    /// See DebugMode for what each mode shows.
+    ///
+    /// The debug shaders work with the current vertex shader (see
+    /// `reload_shaders`), but replace any custom fragment shader while active.
+    pub fn set_debug_mode(&mut self, debug_mode: DebugMode) -> Result<()> {
+        let frag_spirv = match debug_mode {
+            DebugMode::Normal => None,
+            DebugMode::ShowUv => Some(shaders::DEBUG_UV_FRAG),
+            DebugMode::ShowOverdraw => Some(shaders::OVERDRAW_FRAG),
+        };
+        self.debug_fs_module = match frag_spirv {
+            Some(spirv) => Some(create_shader_module(
+                &self.device,
+                spirv,
+                "fragment shader",
+            )?),
+            None => None,
+        };
+        self.debug_mode = debug_mode;
+        self.rebuild_pipelines();
+        Ok(())
+    }
+
+    /// The constant and slope-scaled depth bias (see set_depth_bias)
+    pub fn depth_bias(&self) -> (i32, f32) {
+        (self.depth_bias, self.depth_bias_slope_scale)
+    }
+
+    /// Sets the depth bias added to every sprite's depth when depth testing
+    /// is enabled (`(0, 0.0)` by default), e.g. to stop sprites with the same
+    /// `z` from flickering as they fight over which is in front.
+    ///
+    /// `constant` is in units of the smallest difference in depth that the depth
+    /// buffer can represent, and `slope` is multiplied with how steeply the depth
+    /// changes across a sprite (which is always 0 for a2d's flat sprites).
+    /// The bias applies to everything drawn, so to move some sprites relative
+    /// to others, draw them at a slightly different `z` instead.
+    ///
+    /// This has no effect unless depth testing is enabled (see `enable_depth`)
+    pub fn set_depth_bias(&mut self, constant: i32, slope: f32) {
+        self.depth_bias = constant;
+        self.depth_bias_slope_scale = slope;
+        self.rebuild_pipelines();
+    }
+
+    pub fn depth_enabled(&self) -> bool {
+        self.depth_enabled
+    }
+
+    /// Enables or disables depth testing (disabled by default)
+    ///
+    /// With depth testing enabled, sprites with a smaller `z` (see `Instance::set_z`)
+    /// are drawn in front of sprites with a larger `z`, no matter what order
+    /// they're drawn in. Fully transparent pixels never hide anything, but
+    /// partially transparent sprites still need to be drawn back to front
+    /// to blend correctly.
+    pub fn enable_depth(&mut self, enabled: bool) {
+        self.depth_enabled = enabled;
+        if enabled {
+            let [width, height] = [self.sc_desc.width, self.sc_desc.height];
+            self.ensure_depth_texture(width, height);
+        } else {
+            self.depth_texture = None;
+        }
+        self.dirty = true;
     }
 
     /// By default, the screen coordinates are [0, 0] for the
@@ -139,12 +557,166 @@ impl Graphics2D {
     /// Sets the the scale to set the coordinates of the
     /// lower-right corner (the upper-left is always [0, 0]).
     /// See the method `scale` for more info.
+    ///
+    /// Note that `resized` resets the scale to pixel coordinates,
+    /// so a custom scale needs to be set again after every resize.
     pub fn set_scale(&mut self, new_scale: [f32; 2]) {
+        if imp::pixel_grid_is_stale(self.scale, new_scale) {
+            // The pixel batch has one sprite per unit of the old scale,
+            // so it needs to be regenerated for the new one
+            self.batches[BATCH_SLOT_PIXEL] = None;
+        }
         self.scale = new_scale;
-        self.scale_uniform_buffer = self.device.create_buffer_with_data(
-            bytemuck::cast_slice(&self.scale),
-            wgpu::BufferUsage::UNIFORM,
+        self.update_scale_uniform();
+    }
+
+    pub fn y_axis(&self) -> YAxis {
+        self.y_axis
+    }
+
+    /// Sets which way the y axis points (`YAxis::Down` by default).
+    ///
+    /// With `YAxis::Up`, [0, 0] is the lower-left corner of the window, and
+    /// y grows upwards (like in OpenGL and most math). This applies to
+    /// everything drawn (including the text grid, whose first row ends up at
+    /// the bottom) and to the coordinate conversion methods (e.g.
+    /// `logical_to_world`). Sheets are still drawn right side up.
+    ///
+    /// Flipping the y axis also flips the direction of all rotations, so
+    /// positive rotations are counterclockwise on screen with `YAxis::Up`.
+    pub fn set_y_axis(&mut self, y_axis: YAxis) {
+        self.y_axis = y_axis;
+        self.update_scale_uniform();
+        self.dirty = true;
+    }
+
+    pub fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+
+    /// Rotates everything drawn (`Orientation::Normal` by default),
+    /// e.g. for a display mounted sideways.
+    ///
+    /// The rotation is applied last, after everything else (including
+    /// the y axis). With a quarter turn, the x axis of the scene runs along
+    /// the height of the window and the y axis along its width, so you'll
+    /// usually want to swap the width and height passed to `set_scale`.
+    /// The coordinate conversion methods (e.g. `logical_to_world`) and
+    /// `set_viewport` take the orientation into account.
+    pub fn set_orientation(&mut self, orientation: Orientation) {
+        self.orientation = orientation;
+        self.update_scale_uniform();
+        self.dirty = true;
+    }
+
+    pub fn pixel_snapping(&self) -> bool {
+        self.pixel_snapping
+    }
+
+    /// If enabled, the corners of every sprite are snapped to the nearest
+    /// pixel of the main window. This stops pixel art from shimmering
+    /// when it's drawn at fractional positions (e.g. with a moving camera).
+    ///
+    /// This only really makes sense for sheets drawn with nearest filtering.
+    pub fn set_pixel_snapping(&mut self, pixel_snapping: bool) {
+        self.pixel_snapping = pixel_snapping;
+        self.update_scale_uniform();
+        self.dirty = true;
+    }
+
+    /// The ratio of physical pixels to logical pixels
+    /// (e.g. 2.0 on many high-DPI displays). Defaults to 1.0.
+    pub fn scale_factor(&self) -> f32 {
+        self.scale_factor
+    }
+
+    /// Sets the ratio of physical pixels to logical pixels.
+    /// This should be kept in sync with the window's scale factor
+    /// (e.g. updated on winit's `ScaleFactorChanged` event).
+    ///
+    /// The text grid's character width is in logical pixels, so it's
+    /// resized to keep the same logical width (see `set_text_grid_char_width`).
+    /// Panics if scale_factor isn't positive
+    pub fn set_scale_factor(&mut self, scale_factor: f32) {
+        assert!(
+            scale_factor > 0.0,
+            "Scale factor must be positive (got {})",
+            scale_factor,
         );
+        let text_grid_exists = self.text_grid_dim.is_some()
+            && self.batches[BATCH_SLOT_TEXT].is_some()
+            && self.batches[BATCH_SLOT_TEXT_BG].is_some();
+        if text_grid_exists {
+            let char_width = self.text_grid_scene_char_width() / self.scale_factor;
+            self.scale_factor = scale_factor;
+            self.set_text_grid_scene_char_width(char_width * scale_factor);
+        } else {
+            self.scale_factor = scale_factor;
+        }
+    }
+
+    /// The [width, height] of the drawing area in logical pixels
+    /// (i.e. the physical size passed to `resized` divided by the scale factor)
+    pub fn logical_size(&self) -> [f32; 2] {
+        [
+            self.sc_desc.width as f32 / self.scale_factor,
+            self.sc_desc.height as f32 / self.scale_factor,
+        ]
+    }
+
+    /// Converts a position in logical pixels (relative to the upper-left
+    /// corner of the drawing area) to the coordinates set by `set_scale`
+    pub fn logical_to_world(&self, position: [f32; 2]) -> [f32; 2] {
+        let [x, y] = position;
+        let [width, height] = self.logical_size();
+        let [fx, fy] = self.window_to_scene_fraction([x / width, y / height]);
+        [fx * self.scale[0], fy * self.scale[1]]
+    }
+
+    /// Converts a position in physical pixels (e.g. the position from winit's
+    /// `WindowEvent::CursorMoved`, passed as `[position.x, position.y]`)
+    /// to the coordinates set by `set_scale`
+    pub fn physical_to_world(&self, position: [f64; 2]) -> [f32; 2] {
+        let [x, y] = position;
+        let [width, height] = [self.sc_desc.width as f64, self.sc_desc.height as f64];
+        let [fx, fy] = self.window_to_scene_fraction([(x / width) as f32, (y / height) as f32]);
+        [fx * self.scale[0], fy * self.scale[1]]
+    }
+
+    /// Converts coordinates set by `set_scale` to a position in logical pixels
+    /// (relative to the upper-left corner of the drawing area)
+    pub fn world_to_logical(&self, position: [f32; 2]) -> [f32; 2] {
+        let [x, y] = position;
+        let [width, height] = self.logical_size();
+        let [fx, fy] = self.scene_to_window_fraction([x / self.scale[0], y / self.scale[1]]);
+        [fx * width, fy * height]
+    }
+
+    /// Returns true if the batch at the given slot has changes that haven't
+    /// been uploaded to the GPU yet, i.e. that the next `flush` would upload.
+    /// Batches in slots can't be hidden, so this is exactly whether the batch
+    /// needs an upload (e.g. to skip recomputing a layout that didn't change).
+    /// Layers and MultiSheetBatches, on the other hand, are uploaded
+    /// on every render while they're visible and non-empty.
+    /// Panics if the slot is either out of bounds or there is no
+    /// batch present at the given index
+    pub fn is_batch_dirty(&self, slot: usize) -> bool {
+        self.batches[slot].as_ref().unwrap().is_dirty()
+    }
+
+    /// Discards the changes to the batch at the given slot that haven't been
+    /// uploaded yet, so that the next `flush` skips it (e.g. when the instance
+    /// data is being uploaded some other way).
+    /// Panics if the slot is either out of bounds or there is no
+    /// batch present at the given index
+    pub fn mark_batch_clean(&mut self, slot: usize) {
+        self.batches[slot].as_mut().unwrap().mark_clean();
+    }
+
+    /// Returns how many of the SLOT_LIMIT batch slots currently hold a batch
+    /// (including a2d's own, e.g. BATCH_SLOT_TEXT once text has been drawn)
+    pub fn batch_count(&self) -> usize {
+        self.batches.iter().flatten().count()
     }
 
     /// Returns the number of sprites the batch at the given slot has.
@@ -154,6 +726,533 @@ impl Graphics2D {
         self.batches[slot].as_ref().unwrap().len()
     }
 
+    /// Like `nsprites`, but returns None instead of panicking if the slot is
+    /// either out of bounds or there is no batch present at the given index
+    pub fn try_nsprites(&self, slot: usize) -> Option<usize> {
+        self.batches.get(slot)?.as_ref().map(|batch| batch.len())
+    }
+
+    /// Returns the translation applied to every sprite in the batch
+    /// at the given slot.
+    /// Panics if the slot is either out of bounds or there is no
+    /// batch present at the given index
+    pub fn batch_translation(&self, slot: usize) -> [f32; 2] {
+        self.batches[slot].as_ref().unwrap().translation()
+    }
+
+    /// Moves every sprite in the batch at the given slot at once
+    /// (e.g. for a scrolling layer), without having to update each sprite.
+    /// Panics if the slot is either out of bounds or there is no
+    /// batch present at the given index
+    pub fn set_batch_translation(&mut self, slot: usize, translation: [f32; 2]) {
+        self.batches[slot]
+            .as_mut()
+            .unwrap()
+            .set_translation(translation);
+        self.dirty = true;
+    }
+
+    /// Returns the color multiplied with every sprite in the batch
+    /// at the given slot.
+    /// Panics if the slot is either out of bounds or there is no
+    /// batch present at the given index
+    pub fn batch_tint(&self, slot: usize) -> Color {
+        self.batches[slot].as_ref().unwrap().tint()
+    }
+
+    /// Sets a color to multiply with every sprite in the batch at the
+    /// given slot (e.g. to flash a whole UI panel red, or fade it out).
+    /// This is applied on top of each sprite's own color.
+    /// The default of [1.0, 1.0, 1.0, 1.0] leaves the sprites unchanged.
+    /// Panics if the slot is either out of bounds or there is no
+    /// batch present at the given index
+    pub fn set_batch_tint<C: Into<Color>>(&mut self, slot: usize, tint: C) {
+        self.batches[slot].as_mut().unwrap().set_tint(tint.into());
+        self.dirty = true;
+    }
+
+    /// Returns the range of instances drawn from the batch at the given slot
+    /// (see `set_batch_draw_range`), or None if the whole batch is drawn.
+    /// Panics if the slot is either out of bounds or there is no
+    /// batch present at the given index
+    pub fn batch_draw_range(&self, slot: usize) -> Option<Range<usize>> {
+        self.batches[slot].as_ref().unwrap().draw_range()
+    }
+
+    /// Draws only the instances in the given range from the batch at the
+    /// given slot, without changing the batch itself (e.g. to reveal a
+    /// progress bar made of segment sprites a few at a time).
+    /// The range is clamped to the length of the batch, and None
+    /// (the default) draws the whole batch.
+    /// Panics if the slot is either out of bounds or there is no
+    /// batch present at the given index
+    pub fn set_batch_draw_range(&mut self, slot: usize, range: Option<Range<usize>>) {
+        self.batches[slot].as_mut().unwrap().set_draw_range(range);
+        self.dirty = true;
+    }
+
+    /// Returns the clockwise rotation in radians of the entire batch
+    /// at the given slot (see `set_batch_rotation`).
+    /// Panics if the slot is either out of bounds or there is no
+    /// batch present at the given index
+    pub fn batch_rotation(&self, slot: usize) -> f32 {
+        self.batches[slot].as_ref().unwrap().rotation()
+    }
+
+    /// Rotates the entire batch at the given slot clockwise `rotation`
+    /// radians around its rotation origin (see `set_batch_rotation_origin`).
+    /// This is applied after the batch's translation.
+    /// Panics if the slot is either out of bounds or there is no
+    /// batch present at the given index
+    pub fn set_batch_rotation(&mut self, slot: usize, rotation: f32) {
+        self.batches[slot].as_mut().unwrap().set_rotation(rotation);
+        self.dirty = true;
+    }
+
+    /// Returns the point the batch at the given slot rotates around.
+    /// Panics if the slot is either out of bounds or there is no
+    /// batch present at the given index
+    pub fn batch_rotation_origin(&self, slot: usize) -> [f32; 2] {
+        self.batches[slot].as_ref().unwrap().rotation_origin()
+    }
+
+    /// Sets the point (in the coordinates set by `set_scale`) that the
+    /// batch at the given slot rotates around, e.g. the center of a radial menu.
+    /// Defaults to [0, 0], the upper-left corner of the screen.
+    /// Panics if the slot is either out of bounds or there is no
+    /// batch present at the given index
+    pub fn set_batch_rotation_origin(&mut self, slot: usize, origin: [f32; 2]) {
+        self.batches[slot]
+            .as_mut()
+            .unwrap()
+            .set_rotation_origin(origin);
+        self.dirty = true;
+    }
+
+    /// Draws the batch at the given slot once per translation in `transforms`
+    /// (each applied on top of the batch's own translation), reusing the same
+    /// instance buffer for every draw. This is cheaper than duplicating
+    /// all the sprites in the batch (e.g. for repeating a tilemap).
+    ///
+    /// Passing an empty slice restores the default of drawing the batch once.
+    /// Panics if the slot is either out of bounds or there is no
+    /// batch present at the given index
+    pub fn set_batch_transforms(&mut self, slot: usize, transforms: &[[f32; 2]]) {
+        self.batches[slot]
+            .as_mut()
+            .unwrap()
+            .set_transforms(transforms.to_vec());
+        self.dirty = true;
+    }
+
+    /// Draws the batch at the given slot `counts[0] * counts[1]` times in a grid
+    /// (see `set_batch_transforms`), the copy in column i and row j being
+    /// translated by `origin + [i * step[0], j * step[1]]`.
+    /// E.g. for a scrolling background, make the batch a single tile, use the
+    /// tile size as `step`, and move `origin` by the scroll amount modulo `step`
+    /// (so that the copies always cover the screen).
+    /// If either count is 0, there are no copies, and the batch goes back
+    /// to being drawn once as usual.
+    ///
+    /// Panics if the slot is either out of bounds or there is no
+    /// batch present at the given index
+    pub fn set_batch_tiling(
+        &mut self,
+        slot: usize,
+        origin: [f32; 2],
+        step: [f32; 2],
+        counts: [u32; 2],
+    ) {
+        let mut transforms = Vec::with_capacity(counts[0] as usize * counts[1] as usize);
+        for j in 0..counts[1] {
+            for i in 0..counts[0] {
+                transforms.push([
+                    origin[0] + i as f32 * step[0],
+                    origin[1] + j as f32 * step[1],
+                ]);
+            }
+        }
+        self.set_batch_transforms(slot, &transforms);
+    }
+
+    pub fn batch_filter(&self, slot: usize) -> Filter {
+        self.batches[slot].as_ref().unwrap().filter()
+    }
+
+    /// Returns the sheet that the batch at the given slot draws from.
+    /// Panics if the slot is either out of bounds or there is no
+    /// batch present at the given index
+    pub fn batch_sheet(&self, slot: usize) -> &Sheet {
+        self.batches[slot].as_ref().unwrap().sheet()
+    }
+
+    /// Makes the batch at the given slot draw from a different sheet, keeping
+    /// all of its sprites (e.g. for palette swaps or a damaged look).
+    ///
+    /// Sprites keep their src rects, which are fractions of the sheet,
+    /// so the new sheet should be laid out the same way as the old one
+    /// (see `Sheet::dimensions` for re-deriving them otherwise).
+    /// Panics if the slot is either out of bounds or there is no
+    /// batch present at the given index
+    pub fn set_batch_sheet(&mut self, slot: usize, sheet: &Arc<Sheet>) {
+        self.batches[slot]
+            .as_mut()
+            .unwrap()
+            .set_sheet(sheet.clone());
+        self.dirty = true;
+    }
+
+    /// Returns the palette of the batch at the given slot, if any
+    /// (see `set_batch_palette`).
+    /// Panics if the slot is either out of bounds or there is no
+    /// batch present at the given index
+    pub fn batch_palette(&self, slot: usize) -> Option<&Sheet> {
+        self.batches[slot].as_ref().unwrap().palette()
+    }
+
+    /// Draws the batch at the given slot through a palette, for palette
+    /// swap effects: the red channel of each pixel of the batch's sheet
+    /// (0 to 255) is an index into the top row of `palette`, and the color
+    /// at that index is drawn instead (keeping the alpha of both).
+    /// Typically the palette is a sheet a single pixel tall
+    /// (e.g. from `Sheet::from_colors`), and swapping it recolors the batch.
+    /// Indices past the width of the palette use its last color
+    /// (or wrap around, if it was created with SheetUsage::repeat).
+    /// Pass None (the default) to draw the sheet's own colors.
+    ///
+    /// Panics if the slot is either out of bounds or there is no
+    /// batch present at the given index
+    pub fn set_batch_palette(&mut self, slot: usize, palette: Option<&Arc<Sheet>>) {
+        self.batches[slot]
+            .as_mut()
+            .unwrap()
+            .set_palette(palette.cloned());
+        self.dirty = true;
+    }
+
+    /// Sets how the sheet of the batch at the given slot is sampled
+    /// (e.g. Filter::Nearest for crisp pixel art), independently of any other
+    /// batches drawing from the same sheet.
+    ///
+    /// Panics if the slot is either out of bounds or there is no
+    /// batch present at the given index
+    pub fn set_batch_filter(&mut self, slot: usize, filter: Filter) {
+        self.batches[slot].as_mut().unwrap().set_filter(filter);
+        self.dirty = true;
+    }
+
+    /// Names the batch at the given slot in GPU debugging tools
+    /// (e.g. RenderDoc captures), so that it can be told apart from other batches.
+    ///
+    /// Panics if the slot is either out of bounds or there is no
+    /// batch present at the given index
+    pub fn set_batch_label(&mut self, slot: usize, label: &str) {
+        self.batches[slot].as_mut().unwrap().set_label(label);
+    }
+
+    /// Applies `transform` on top of the batch at the given slot's own
+    /// translation, rotation and scale for the next render only
+    /// (e.g. for a screen shake or a hit flash), without changing the batch.
+    /// Scales are multiplied, and translations and rotations are added.
+    ///
+    /// Panics if the slot is either out of bounds or there is no
+    /// batch present at the given index
+    pub fn transform_batch_once(&mut self, slot: usize, transform: Transform2D) {
+        self.batches[slot]
+            .as_mut()
+            .unwrap()
+            .set_frame_transform(transform);
+        self.dirty = true;
+    }
+
+    /// Queues a sprite to be drawn on the next render.
+    ///
+    /// `dst` is the rectangle to draw to (in the coordinates set by `set_scale`),
+    /// `src` is the rectangle to crop from the sheet (coordinates between 0 and 1),
+    /// and `rotation` is the clockwise rotation in radians around the center of `dst`.
+    ///
+    /// Queued sprites are drawn above all layers and beneath all batches,
+    /// and are cleared once they have been rendered, so they need to be
+    /// drawn again every frame.
+    /// Consecutive sprites drawn from the same sheet share a single draw call.
+    pub fn draw_sprite<R1: Into<Rect>, R2: Into<Rect>>(
+        &mut self,
+        sheet: &Arc<Sheet>,
+        dst: R1,
+        src: R2,
+        rotation: f32,
+    ) {
+        let instance = Instance::builder()
+            .src(src)
+            .dest(dst)
+            .rotate(rotation)
+            .build();
+        self.queue_instance(sheet, instance);
+    }
+
+    /// Queues the given sprite to be drawn on the next render
+    /// (see `draw_sprite`)
+    pub fn draw(&mut self, sprite: &Sprite) {
+        self.queue_instance(sprite.sheet(), *sprite.instance());
+    }
+
+    /// Queues the entire sheet to be drawn stretched across the whole drawing
+    /// area (from [0, 0] to `scale()`) on the next render, e.g. for a background.
+    ///
+    /// Unlike `draw_sprite`, this is drawn beneath all other sprites queued
+    /// for the next render, no matter which was queued first
+    /// (but still above all layers).
+    pub fn draw_fullscreen(&mut self, sheet: &Arc<Sheet>) {
+        let [width, height] = self.scale;
+        let instance = Instance::builder().dest([0.0, 0.0, width, height]).build();
+        self.immediate_sprites
+            .insert(0, (sheet.clone(), vec![instance]));
+        self.dirty = true;
+    }
+
+    /// Queues sprites described by each of `instances` to be drawn on the
+    /// next render, in order (see `draw_sprite`). Takes any iterator,
+    /// e.g. `entities.iter().map(|e| e.instance())`, so there's no need
+    /// to collect the instances first
+    pub fn draw_instances<I: IntoIterator<Item = Instance>>(
+        &mut self,
+        sheet: &Arc<Sheet>,
+        instances: I,
+    ) {
+        for instance in instances {
+            self.queue_instance(sheet, instance);
+        }
+    }
+
+    /// Adds a new layer with the given name, drawn above all previously
+    /// added layers. If a layer with the name already exists,
+    /// that layer is returned instead.
+    ///
+    /// Layers let you keep sprites around across renders
+    /// (see `Layer` and `draw_sprite`).
+    pub fn add_layer(&mut self, name: &str) -> &mut Layer {
+        self.dirty = true;
+        match self.layers.iter().position(|layer| layer.name() == name) {
+            Some(i) => &mut self.layers[i],
+            None => {
+                self.layers.push(Layer::new(name));
+                self.layers.last_mut().unwrap()
+            }
+        }
+    }
+
+    /// Returns the layer with the given name, if it exists
+    pub fn layer_mut(&mut self, name: &str) -> Option<&mut Layer> {
+        self.dirty = true;
+        self.layers.iter_mut().find(|layer| layer.name() == name)
+    }
+
+    /// The names of all layers, in the order they are drawn (bottom first)
+    pub fn layer_names(&self) -> Vec<&str> {
+        self.layers.iter().map(|layer| layer.name()).collect()
+    }
+
+    /// The names of all multi-sheet batches, in the order they are drawn
+    /// (bottom first)
+    pub fn multi_sheet_batch_names(&self) -> Vec<&str> {
+        self.multi_sheet_batches
+            .iter()
+            .map(|batch| batch.name())
+            .collect()
+    }
+
+    /// Adds a new MultiSheetBatch with the given name, drawing from the given
+    /// sheets (at most MAX_SHEETS_PER_BATCH), and drawn above all previously
+    /// added multi-sheet batches. If one with the name already exists, it is
+    /// replaced (keeping its place in the draw order).
+    ///
+    /// Returns an error if there are no sheets, too many sheets, or any of the
+    /// sheets were created with a different Graphics2D
+    pub fn add_multi_sheet_batch(
+        &mut self,
+        name: &str,
+        sheets: &[Arc<Sheet>],
+    ) -> Result<&mut MultiSheetBatch> {
+        let batch = MultiSheetBatch::new(self, name, sheets)?;
+        self.dirty = true;
+        match self
+            .multi_sheet_batches
+            .iter()
+            .position(|batch| batch.name() == name)
+        {
+            Some(i) => {
+                self.multi_sheet_batches[i] = batch;
+                Ok(&mut self.multi_sheet_batches[i])
+            }
+            None => {
+                self.multi_sheet_batches.push(batch);
+                Ok(self.multi_sheet_batches.last_mut().unwrap())
+            }
+        }
+    }
+
+    /// Returns the multi-sheet batch with the given name, if it exists
+    pub fn multi_sheet_batch_mut(&mut self, name: &str) -> Option<&mut MultiSheetBatch> {
+        self.dirty = true;
+        self.multi_sheet_batches
+            .iter_mut()
+            .find(|batch| batch.name() == name)
+    }
+
+    /// Moves the sprites of each of the `sources` layers (in order) to the end
+    /// of the `target` layer, and removes the `sources` layers
+    /// (see `Layer::append`).
+    ///
+    /// Returns an error (without changing anything) if any of the layers
+    /// don't exist, or if `target` is also one of the `sources`
+    pub fn merge_layers(&mut self, target: &str, sources: &[&str]) -> Result<()> {
+        for name in std::iter::once(&target).chain(sources) {
+            if !self.layers.iter().any(|layer| layer.name() == *name) {
+                err!("No layer named {:?}", name);
+            }
+        }
+        if sources.contains(&target) {
+            err!("Tried to merge layer {:?} into itself", target);
+        }
+        let mut merged = Layer::new(target);
+        for source in sources {
+            if let Some(i) = self.layers.iter().position(|layer| layer.name() == *source) {
+                let mut layer = self.layers.remove(i);
+                merged.append(&mut layer);
+            }
+        }
+        let target = self.layer_mut(target).unwrap();
+        target.append(&mut merged);
+        Ok(())
+    }
+
+    /// Queues a solid colored rectangle to be drawn on the next render
+    /// (see `draw_sprite`)
+    pub fn fill_rect<R: Into<Rect>, C: Into<Color>>(&mut self, rect: R, color: C) -> Result<()> {
+        let sheet = self.white_sheet()?;
+        let instance = Instance::builder().dest(rect).color_factor(color).build();
+        self.queue_instance(&sheet, instance);
+        Ok(())
+    }
+
+    /// Queues the border of a rectangle to be drawn on the next render
+    /// (see `draw_sprite`)
+    ///
+    /// The border is drawn inside the rectangle, `thickness` units thick.
+    pub fn draw_rect_outline<R: Into<Rect>, C: Into<Color>>(
+        &mut self,
+        rect: R,
+        thickness: f32,
+        color: C,
+    ) -> Result<()> {
+        let rect = rect.into();
+        let [x1, y1] = rect.upper_left();
+        let [x2, y2] = rect.lower_right();
+        let color = color.into();
+        let t = thickness;
+        let sides = [
+            Rect::new(x1, y1, x2, y1 + t),
+            Rect::new(x1, y2 - t, x2, y2),
+            Rect::new(x1, y1 + t, x1 + t, y2 - t),
+            Rect::new(x2 - t, y1 + t, x2, y2 - t),
+        ];
+        // sides may be degenerate if the border is as thick as the rectangle itself
+        for side in sides.iter().flatten() {
+            self.fill_rect(*side, color)?;
+        }
+        Ok(())
+    }
+
+    /// Queues a UI panel (a filled rectangle with a border of a different color)
+    /// to be drawn on the next render (see `draw_sprite` and `draw_rect_outline`)
+    pub fn draw_panel<R: Into<Rect>, C1: Into<Color>, C2: Into<Color>>(
+        &mut self,
+        rect: R,
+        fill: C1,
+        border: C2,
+        thickness: f32,
+    ) -> Result<()> {
+        let rect = rect.into();
+        self.fill_rect(rect, fill)?;
+        self.draw_rect_outline(rect, thickness, border)
+    }
+
+    pub fn line_cap(&self) -> LineCap {
+        self.line_cap
+    }
+
+    /// Sets how the ends of lines drawn with `draw_line` and `draw_polyline`
+    /// look. Defaults to `LineCap::Butt`
+    pub fn set_line_cap(&mut self, cap: LineCap) {
+        self.line_cap = cap;
+    }
+
+    pub fn line_join(&self) -> LineJoin {
+        self.line_join
+    }
+
+    /// Sets how the corners of lines drawn with `draw_polyline` look.
+    /// Defaults to `LineJoin::Miter`
+    pub fn set_line_join(&mut self, join: LineJoin) {
+        self.line_join = join;
+    }
+
+    /// Queues a straight line between two points to be drawn on the next render
+    /// (see `draw_sprite` and `draw_polyline`)
+    pub fn draw_line<P1: Into<Point>, P2: Into<Point>, C: Into<Color>>(
+        &mut self,
+        start: P1,
+        end: P2,
+        thickness: f32,
+        color: C,
+    ) -> Result<()> {
+        self.draw_polyline(&[start.into(), end.into()], thickness, color)
+    }
+
+    /// Queues connected line segments through the given points to be drawn
+    /// on the next render (see `draw_sprite`)
+    ///
+    /// Positions are not rounded, so lines may start and end between pixels.
+    /// The ends and corners are drawn according to `line_cap` and `line_join`.
+    /// Zero length segments are skipped.
+    ///
+    /// Only round caps and joins are anti-aliased: the segments themselves
+    /// (and square caps and bevel joins) have hard edges, so a thin line
+    /// with round caps or joins will show both kinds of edges.
+    pub fn draw_polyline<C: Into<Color>>(
+        &mut self,
+        points: &[Point],
+        thickness: f32,
+        color: C,
+    ) -> Result<()> {
+        let color = color.into();
+        let pieces = line::polyline_pieces(points, thickness, self.line_cap, self.line_join);
+        for piece in pieces {
+            match piece {
+                LinePiece::Quad(mut instance) => {
+                    let sheet = self.white_sheet()?;
+                    instance.set_color_factor(color);
+                    self.queue_instance(&sheet, instance);
+                }
+                LinePiece::Disk(center, diameter) => {
+                    let sheet = self.disk_sheet()?;
+                    let radius = diameter / 2.0;
+                    let instance = Instance::builder()
+                        .dest([
+                            center.x - radius,
+                            center.y - radius,
+                            center.x + radius,
+                            center.y + radius,
+                        ])
+                        .color_factor(color)
+                        .build();
+                    self.queue_instance(&sheet, instance);
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Uses the builtin pixel batch to draw a pixel of the given color at the
     /// given location
     ///
@@ -167,18 +1266,40 @@ impl Graphics2D {
     /// is to draw pixel by pixel and how much it might heat up their computer
     /// and make their computer's fans go crazy is something I'd like to support.
     ///
+    /// Pixels outside of the drawing area are ignored.
+    /// Like all other updates, changes only show up after calling `flush`.
+    ///
     pub fn set_pixel<C: Into<Color>>(&mut self, x: usize, y: usize, color: C) -> Result<()> {
-        let [width, _] = self.scale();
-        let width = width as usize;
-        let inst_index = y * width + x;
-        self.pixel_batch()?.get(inst_index).color(color);
+        let [width, height] = imp::pixel_grid_dim(self.scale());
+        if x < width && y < height {
+            let inst_index = y * width + x;
+            self.pixel_batch()?.get(inst_index).color(color);
+        }
         Ok(())
     }
 
+    /// Resets every pixel drawn with `set_pixel` back to transparent
+    pub fn clear_pixels(&mut self) {
+        // The pixel batch starts out transparent when it is (lazily) recreated
+        self.batches[BATCH_SLOT_PIXEL] = None;
+        self.dirty = true;
+    }
+
+    /// The number of rows and columns in the text grid, or None if it
+    /// hasn't been initialized yet (it is initialized by `init_text_grid`,
+    /// or with DEFAULT_TEXT_NCOLS columns by the first text method called)
+    pub fn text_grid_dim(&self) -> Option<TextGridDim> {
+        self.text_grid_dim
+    }
+
     /// Initialize the builtin text batch to cover the entire drawing area.
     ///
     /// The grid will be sized so that there will be exactly 'ncols' columns
     ///
+    /// Calling this first is optional: on a fresh Graphics2D, the first
+    /// text method called (e.g. `draw_char`) initializes the grid with
+    /// DEFAULT_TEXT_NCOLS columns instead of failing.
+    ///
     pub fn init_text_grid(&mut self, ncols: usize) -> Result<TextGridDim> {
         let [width, height] = self.scale();
         let step_width = width / (ncols + 1) as f32;
@@ -187,6 +1308,7 @@ impl Graphics2D {
         let dest_height = step_height * 2.0;
         let nrows = (height / step_height) as usize;
         let mut descs = vec![];
+        let mut bg_descs = vec![];
         for row in 0..nrows {
             let y = step_height * (row as f32);
             for col in 0..ncols {
@@ -197,26 +1319,76 @@ impl Graphics2D {
                     dst: [x, y, x + dest_width, y + dest_height].into(),
                     rotate: 0.0,
                 });
+                bg_descs.push(SpriteDesc {
+                    color: [0.0, 0.0, 0.0, 0.0].into(),
+                    src: 0,
+                    dst: [x, y, x + step_width, y + step_height].into(),
+                    rotate: 0.0,
+                });
             }
         }
         let sheet = Sheet::from_bytes(self, res::COURIER_CHARMAP)?;
-        self.batches[BATCH_SLOT_TEXT] = Some(Batch::new(
-            self,
-            sheet,
-            res::CHARMAP_NROWS,
-            res::CHARMAP_NCOLS,
-            &descs,
-        ));
+        let mut batch = Batch::new(self, sheet, res::CHARMAP_NROWS, res::CHARMAP_NCOLS, &descs);
+        batch.set_label("a2d_text_batch");
+        self.batches[BATCH_SLOT_TEXT] = Some(batch);
+        let bg_sheet = self.white_sheet()?;
+        let mut bg_batch = Batch::new(self, bg_sheet, 1, 1, &bg_descs);
+        bg_batch.set_label("a2d_text_bg_batch");
+        self.batches[BATCH_SLOT_TEXT_BG] = Some(bg_batch);
+        self.update_text_underlays();
         let dim = TextGridDim { nrows, ncols };
         self.text_grid_dim = Some(dim);
+        self.text_grid_base_char_width = step_width;
         Ok(dim)
     }
 
+    /// Returns the position of the upper-left corner of the text grid
+    pub fn text_grid_position(&mut self) -> Result<[f32; 2]> {
+        self.ensure_text_grid()?;
+        Ok(self.text_batch()?.translation())
+    }
+
+    /// Moves the text grid so that its upper-left corner is at `position`
+    /// (e.g. to anchor a console to the bottom of the screen)
+    pub fn set_text_grid_position(&mut self, position: [f32; 2]) -> Result<()> {
+        self.ensure_text_grid()?;
+        self.text_batch()?.set_translation(position);
+        self.text_bg_batch()?.set_translation(position);
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Returns the width of a single character cell of the text grid,
+    /// in logical pixels (see `set_text_grid_char_width`)
+    pub fn text_grid_char_width(&mut self) -> Result<f32> {
+        self.ensure_text_grid()?;
+        Ok(self.text_grid_scene_char_width() / self.scale_factor)
+    }
+
+    /// Resizes the text grid so that each character cell is `char_width` wide.
+    /// The height of each cell is scaled proportionally.
+    ///
+    /// The width is in logical pixels: it's multiplied by the scale factor
+    /// (see `set_scale_factor`), so that text comes out the same physical size
+    /// on displays with different DPI (with the default scale of one unit
+    /// per physical pixel).
+    pub fn set_text_grid_char_width(&mut self, char_width: f32) -> Result<()> {
+        self.ensure_text_grid()?;
+        self.set_text_grid_scene_char_width(char_width * self.scale_factor);
+        Ok(())
+    }
+
+    /// Returns the [width, height] of the entire text grid, in the coordinates
+    /// set by `set_scale` (useful for aligning the grid to the edges of the screen)
+    pub fn text_grid_pixel_size(&mut self) -> Result<[f32; 2]> {
+        let TextGridDim { nrows, ncols } = self.ensure_text_grid()?;
+        let char_width = self.text_grid_scene_char_width();
+        let char_height = res::CHAR_HEIGHT_TO_WIDTH_RATIO * char_width;
+        Ok([ncols as f32 * char_width, nrows as f32 * char_height])
+    }
+
     pub fn draw_char(&mut self, row: usize, col: usize, ch: char) -> Result<()> {
-        if self.text_grid_dim.is_none() {
-            self.init_text_grid(DEFAULT_TEXT_NCOLS)?;
-        }
-        let TextGridDim { nrows, ncols } = self.text_grid_dim.unwrap();
+        let TextGridDim { nrows, ncols } = self.ensure_text_grid()?;
         if row < nrows && col < ncols {
             let instance_index = ncols * row + col;
             if let Some(src) = res::char_to_charmap_index(ch) {
@@ -233,10 +1405,213 @@ impl Graphics2D {
         }
         Ok(())
     }
+
+    /// Adds a line of text to the bottom of the text grid, scrolling
+    /// everything else up by one row (like a console or log).
+    /// Lines longer than the grid is wide are wrapped onto multiple rows.
+    ///
+    /// The last `TEXT_SCROLLBACK_LIMIT` rows are kept so that they can be
+    /// viewed again with `scroll_text`. Adding a line scrolls back down
+    /// to the bottom. This redraws the whole grid, so it doesn't mix well
+    /// with `draw_text`. Like `draw_char`, this only shows up after `flush`
+    pub fn push_text_line(&mut self, text: &str) -> Result<()> {
+        let TextGridDim { ncols, .. } = self.ensure_text_grid()?;
+        let chars: Vec<_> = text.chars().collect();
+        if chars.is_empty() {
+            self.text_scrollback.push_back(String::new());
+        }
+        for chunk in chars.chunks(ncols.max(1)) {
+            self.text_scrollback.push_back(chunk.iter().collect());
+        }
+        while self.text_scrollback.len() > TEXT_SCROLLBACK_LIMIT {
+            self.text_scrollback.pop_front();
+        }
+        self.text_scroll = 0;
+        self.redraw_text_scrollback()
+    }
+
+    /// Moves the view of the lines added with `push_text_line`
+    /// up (positive `delta_rows`) into older history, or back down (negative)
+    pub fn scroll_text(&mut self, delta_rows: i32) -> Result<()> {
+        let TextGridDim { nrows, .. } = self.ensure_text_grid()?;
+        let max_scroll = self.text_scrollback.len().saturating_sub(nrows) as i64;
+        let scroll = (self.text_scroll as i64 + delta_rows as i64)
+            .max(0)
+            .min(max_scroll);
+        self.text_scroll = scroll as usize;
+        self.redraw_text_scrollback()
+    }
+
+    /// How many rows up from the bottom the text grid is scrolled
+    /// (see `scroll_text`)
+    pub fn text_scroll(&self) -> usize {
+        self.text_scroll
+    }
+
+    /// Like `draw_text`, but also sets the foreground color of each
+    /// character drawn (see `set_text_fg`)
+    pub fn draw_colored_text<C: Into<Color>>(
+        &mut self,
+        row: usize,
+        col: usize,
+        text: &str,
+        color: C,
+    ) -> Result<()> {
+        let color = color.into();
+        self.draw_text(row, col, text)?;
+        for c in col..col + text.chars().count() {
+            self.set_text_fg(row, c, color)?;
+        }
+        Ok(())
+    }
+
+    /// Sets the color of the character in the given cell of the text grid
+    /// (white by default). Like `draw_char`, this only shows up after `flush`
+    pub fn set_text_fg<C: Into<Color>>(&mut self, row: usize, col: usize, color: C) -> Result<()> {
+        let TextGridDim { nrows, ncols } = self.ensure_text_grid()?;
+        if row < nrows && col < ncols {
+            self.text_batch()?.get(ncols * row + col).color(color);
+        }
+        Ok(())
+    }
+
+    pub fn text_shadow(&self) -> Option<([f32; 2], Color)> {
+        self.text_shadow
+    }
+
+    /// Draws a drop shadow beneath all text in the text grid (none by default):
+    /// a copy of the text moved by `offset` (in the coordinates set by
+    /// `set_scale`, e.g. `[1.0, 1.0]` for down and to the right) and drawn
+    /// entirely in the given color.
+    ///
+    /// Each glyph is drawn once more, so this doubles the cost of drawing the text
+    pub fn set_text_shadow(&mut self, shadow: Option<([f32; 2], Color)>) {
+        self.text_shadow = shadow;
+        self.update_text_underlays();
+    }
+
+    pub fn text_outline(&self) -> Option<(f32, Color)> {
+        self.text_outline
+    }
+
+    /// Draws an outline of the given width (in the coordinates set by
+    /// `set_scale`) and color around all text in the text grid (none by default),
+    /// above any shadow.
+    ///
+    /// The outline is made by drawing each glyph 8 more times, shifted in
+    /// every direction, so this makes the text about 9 times as expensive
+    /// to draw. Widths much larger than the strokes of the font leave gaps
+    pub fn set_text_outline(&mut self, outline: Option<(f32, Color)>) {
+        self.text_outline = outline;
+        self.update_text_underlays();
+    }
+
+    /// Sets the color of the background of the given cell of the text grid
+    /// (transparent by default). Like `draw_char`, this only shows up after `flush`
+    pub fn set_text_bg<C: Into<Color>>(&mut self, row: usize, col: usize, color: C) -> Result<()> {
+        let TextGridDim { nrows, ncols } = self.ensure_text_grid()?;
+        if row < nrows && col < ncols {
+            self.text_bg_batch()?.get(ncols * row + col).color(color);
+        }
+        Ok(())
+    }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct TextGridDim {
     pub nrows: usize,
     pub ncols: usize,
 }
+
+/// Statistics about a single rendered frame, useful when tuning batching
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RenderStats {
+    /// Number of draw calls issued
+    pub draw_calls: usize,
+
+    /// Total number of sprite instances drawn, across all draw calls
+    pub total_instances: usize,
+
+    /// Number of batches (including groups of sprites queued
+    /// with draw_sprite) that were drawn
+    pub batches_drawn: usize,
+
+    /// Number of transient GPU buffers created for the frame
+    pub buffers_allocated: usize,
+}
+
+/// Which way the y axis points on screen (see Graphics2D::set_y_axis)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum YAxis {
+    /// y grows downwards, with [0, 0] at the upper-left corner
+    Down,
+
+    /// y grows upwards, with [0, 0] at the lower-left corner
+    Up,
+}
+
+/// Color formats that a2d may render to (see Graphics2D::surface_format).
+/// The names match those of the corresponding `wgpu::TextureFormat`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SurfaceFormat {
+    Bgra8UnormSrgb,
+    Bgra8Unorm,
+    Rgba8UnormSrgb,
+    Rgba8Unorm,
+}
+
+impl SurfaceFormat {
+    pub(super) fn from_wgpu(format: wgpu::TextureFormat) -> Self {
+        match format {
+            wgpu::TextureFormat::Bgra8UnormSrgb => SurfaceFormat::Bgra8UnormSrgb,
+            wgpu::TextureFormat::Bgra8Unorm => SurfaceFormat::Bgra8Unorm,
+            wgpu::TextureFormat::Rgba8UnormSrgb => SurfaceFormat::Rgba8UnormSrgb,
+            wgpu::TextureFormat::Rgba8Unorm => SurfaceFormat::Rgba8Unorm,
+            format => panic!("a2d doesn't render to {:?}", format),
+        }
+    }
+}
+
+/// How the output is rotated on screen (see Graphics2D::set_orientation).
+/// All rotations are clockwise
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    Normal,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+impl Orientation {
+    pub(super) fn quarter_turns(self) -> u32 {
+        match self {
+            Orientation::Normal => 0,
+            Orientation::Rotate90 => 1,
+            Orientation::Rotate180 => 2,
+            Orientation::Rotate270 => 3,
+        }
+    }
+}
+
+/// Alternate ways of drawing sprites, for debugging (see Graphics2D::set_debug_mode).
+///
+/// There is no wireframe mode, since wgpu 0.5 can't draw polygons as lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugMode {
+    /// Draws sprites as usual
+    Normal,
+
+    /// Draws the coordinates each pixel is sampled from (between 0 and 1
+    /// across the entire sheet) as red (x) and green (y), in place of the
+    /// color of the sheet. Useful for spotting flipped or offset source rects
+    ShowUv,
+
+    /// Draws every sprite as a faint white rectangle, with overlapping
+    /// sprites adding up, so that the brightest areas are the ones
+    /// drawn over the most times
+    ShowOverdraw,
+}
+
+/// Identifies a window added with Graphics2D::add_surface
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SurfaceId(usize);
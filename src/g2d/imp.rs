@@ -1,28 +1,51 @@
 use super::*;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 
 /// Call wgpu's device.poll(..) roughly 60 times per second
 const POLL_SLEEP_DUR: Duration = Duration::from_micros((1000000.0 / 60.0) as u64);
 
+/// Source of unique values for Graphics2D::texture_layout_tag
+static NEXT_LAYOUT_TAG: AtomicUsize = AtomicUsize::new(0);
+
 /// Helper methods on Graphics2D (all listed here should be private to a2d)
 impl Graphics2D {
     pub(super) async fn new0<W: HasRawWindowHandle>(
         physical_width: u32,
         physical_height: u32,
         window: &W,
+        adapter_index: Option<usize>,
     ) -> Result<Self> {
         let surface = wgpu::Surface::create(window);
-        let adapter = match wgpu::Adapter::request(
-            &wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::Default,
-                compatible_surface: Some(&surface),
+        let adapter = match adapter_index {
+            Some(index) => {
+                let mut adapters = wgpu::Adapter::enumerate(BACKENDS);
+                if index >= adapters.len() {
+                    err!(
+                        "Adapter index {} out of bounds ({} adapters available)",
+                        index,
+                        adapters.len(),
+                    );
+                }
+                adapters.swap_remove(index)
+            }
+            None => match wgpu::Adapter::request(
+                &wgpu::RequestAdapterOptions {
+                    power_preference: wgpu::PowerPreference::Default,
+                    compatible_surface: Some(&surface),
+                },
+                BACKENDS,
+            )
+            .await
+            {
+                Some(adapter) => adapter,
+                None => err!(
+                    "No graphics adapter that can draw to this window was found \
+                    (tried Vulkan, Metal and DX12)"
+                ),
             },
-            wgpu::BackendBit::PRIMARY,
-        )
-        .await
-        {
-            Some(adapter) => adapter,
-            None => err!(""),
         };
+        let adapter_info = adapter.get_info();
         let (device, queue) = adapter
             .request_device(&wgpu::DeviceDescriptor {
                 extensions: wgpu::Extensions {
@@ -31,6 +54,10 @@ impl Graphics2D {
                 limits: Default::default(),
             })
             .await;
+        // wgpu 0.5 has no way to ask which formats the surface supports, so we
+        // can't pick a different one up front. wgpu does check the format itself
+        // when creating the swap chain, and panics with the list of supported
+        // formats if it isn't one of them (rather than producing garbage).
         let sc_desc = wgpu::SwapChainDescriptor {
             usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
             format: wgpu::TextureFormat::Bgra8UnormSrgb,
@@ -40,10 +67,12 @@ impl Graphics2D {
         };
         let swap_chain = device.create_swap_chain(&surface, &sc_desc);
         // compile shaders
-        let vs_data = wgpu::read_spirv(std::io::Cursor::new(shaders::VERT))?;
-        let fs_data = wgpu::read_spirv(std::io::Cursor::new(shaders::FRAG))?;
-        let vs_module = device.create_shader_module(&vs_data);
-        let fs_module = device.create_shader_module(&fs_data);
+        let vs_module = create_shader_module(&device, shaders::VERT, "vertex shader")?;
+        let fs_module = create_shader_module(&device, shaders::FRAG, "fragment shader")?;
+        let multi_fs_module =
+            create_shader_module(&device, shaders::MULTI_FRAG, "fragment shader")?;
+        let palette_fs_module =
+            create_shader_module(&device, shaders::PALETTE_FRAG, "fragment shader")?;
 
         // sheet bind layout
         let texture_bind_group_layout =
@@ -67,6 +96,30 @@ impl Graphics2D {
                 label: Some("texture_bind_group_layout"),
             });
 
+        // multi-sheet bind layout: one texture per sheet, then a shared sampler
+        let multi_texture_bind_group_layout = {
+            let mut bindings: Vec<_> = (0..MAX_SHEETS_PER_BATCH)
+                .map(|i| wgpu::BindGroupLayoutEntry {
+                    binding: i as u32,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::SampledTexture {
+                        multisampled: false,
+                        dimension: wgpu::TextureViewDimension::D2,
+                        component_type: wgpu::TextureComponentType::Uint,
+                    },
+                })
+                .collect();
+            bindings.push(wgpu::BindGroupLayoutEntry {
+                binding: MAX_SHEETS_PER_BATCH as u32,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::Sampler { comparison: false },
+            });
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                bindings: &bindings,
+                label: Some("multi_texture_bind_group_layout"),
+            })
+        };
+
         // scale uniform bind layout
         let scale_uniform_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -89,81 +142,487 @@ impl Graphics2D {
                 label: Some("translation_uniform_bind_group_layout"),
             });
 
-        // build the pipeline
-        let render_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        // build the pipelines
+        let pipeline_layouts = PipelineLayouts {
+            sprite: device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 bind_group_layouts: &[
                     &texture_bind_group_layout,
                     &scale_uniform_bind_group_layout,
                     &translation_uniform_bind_group_layout,
                 ],
-            });
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            layout: &render_pipeline_layout,
-            vertex_stage: wgpu::ProgrammableStageDescriptor {
-                module: &vs_module,
-                entry_point: "main",
-            },
-            fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
-                module: &fs_module,
-                entry_point: "main",
             }),
-            rasterization_state: Some(wgpu::RasterizationStateDescriptor {
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: wgpu::CullMode::Back,
-                depth_bias: 0,
-                depth_bias_slope_scale: 0.0,
-                depth_bias_clamp: 0.0,
+            multi: device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                bind_group_layouts: &[
+                    &multi_texture_bind_group_layout,
+                    &scale_uniform_bind_group_layout,
+                    &translation_uniform_bind_group_layout,
+                ],
+            }),
+            palette: device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                bind_group_layouts: &[
+                    &texture_bind_group_layout,
+                    &scale_uniform_bind_group_layout,
+                    &translation_uniform_bind_group_layout,
+                    &texture_bind_group_layout,
+                ],
             }),
-            color_states: &[wgpu::ColorStateDescriptor {
+        };
+        let pipelines = Pipelines::new(
+            &device,
+            &pipeline_layouts,
+            &vs_module,
+            &fs_module,
+            &multi_fs_module,
+            &palette_fs_module,
+            PipelineOptions {
                 format: sc_desc.format,
-                color_blend: wgpu::BlendDescriptor {
-                    src_factor: wgpu::BlendFactor::SrcAlpha,
-                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                    operation: wgpu::BlendOperation::Add,
-                },
-                alpha_blend: wgpu::BlendDescriptor::REPLACE,
-                write_mask: wgpu::ColorWrite::ALL,
-            }],
-            primitive_topology: wgpu::PrimitiveTopology::TriangleList,
-            depth_stencil_state: None,
-            vertex_state: wgpu::VertexStateDescriptor {
-                index_format: wgpu::IndexFormat::Uint16,
-                vertex_buffers: &[Instance::desc()],
+                blend: Blend::Alpha,
+                depth_bias: 0,
+                depth_bias_slope_scale: 0.0,
             },
-            sample_count: 1,
-            sample_mask: !0,
-            alpha_to_coverage_enabled: false,
-        });
+        );
+
+        let index_buffer = create_buffer_checked(
+            &device,
+            bytemuck::cast_slice(&QUAD_INDICES),
+            wgpu::BufferUsage::INDEX,
+        );
 
         let scale = [1.0, 1.0];
-        let scale_uniform_buffer = device
-            .create_buffer_with_data(bytemuck::cast_slice(&scale), wgpu::BufferUsage::UNIFORM);
+        let scale_uniform = ScaleUniform {
+            scale,
+            framebuffer_size: [physical_width as f32, physical_height as f32],
+            pixel_snapping: 0.0,
+            y_up: 0.0,
+            quarter_turns: 0.0,
+        };
+        let scale_uniform_buffer = create_buffer_checked(
+            &device,
+            bytemuck::bytes_of(&scale_uniform),
+            wgpu::BufferUsage::UNIFORM,
+        );
 
         Ok(Self {
             surface,
+            adapter_info,
             device: Arc::new(device),
             queue,
             sc_desc,
             swap_chain,
             scale_uniform_bind_group_layout,
             translation_uniform_bind_group_layout,
-            render_pipeline,
+            pipeline_layouts,
+            shader_modules: ShaderModules {
+                vs: vs_module,
+                fs: fs_module,
+                multi_fs: multi_fs_module,
+                palette_fs: palette_fs_module,
+            },
+            pipelines,
+            depth_enabled: false,
+            depth_bias: 0,
+            depth_bias_slope_scale: 0.0,
+            debug_mode: DebugMode::Normal,
+            debug_fs_module: None,
+            debug_pipelines: None,
+            multi_texture_bind_group_layout,
+            viewport: None,
+            clear_on_render: true,
+            depth_texture: None,
+            index_buffer,
             texture_bind_group_layout,
+            texture_layout_tag: NEXT_LAYOUT_TAG.fetch_add(1, Ordering::Relaxed),
             scale,
+            scale_factor: 1.0,
+            y_axis: YAxis::Down,
+            orientation: Orientation::Normal,
+            pixel_snapping: false,
             scale_uniform_buffer,
             batches: Default::default(),
             text_grid_dim: None,
+            text_grid_base_char_width: 1.0,
+            text_scrollback: VecDeque::new(),
+            text_scroll: 0,
+            text_shadow: None,
+            text_outline: None,
+            immediate_sprites: vec![],
+            layers: vec![],
+            multi_sheet_batches: vec![],
+            multi_sheet_scratch: vec![],
+            surfaces: vec![],
+            white_sheet: None,
+            placeholder_sheet: None,
+            disk_sheet: None,
+            line_cap: LineCap::Butt,
+            line_join: LineJoin::Miter,
+            last_frame_stats: RenderStats::default(),
+            target_fps: None,
+            last_frame_time: None,
+            fixed_step_accumulator: Duration::from_secs(0),
+            last_fixed_step_time: None,
+            hidden: false,
+            render_when_hidden: true,
+            max_instances_per_batch: None,
+            error_handler: None,
             dirty: true,
             poll_thread: None,
         })
     }
 
+    /// Draws everything (batches, layers and queued sprites) to the given view
+    pub(super) fn render_to_view(
+        &mut self,
+        view: &wgpu::TextureView,
+        width: u32,
+        height: u32,
+    ) -> Result<()> {
+        self.check_sheet_layouts()?;
+        self.check_instance_counts()?;
+        if self.depth_enabled {
+            self.ensure_depth_texture(width, height);
+        }
+        self.dirty = false;
+        struct BatchInfo<'a> {
+            batch: &'a Batch,
+            instance_buffer: &'a wgpu::Buffer,
+            translation_bind_groups: Vec<wgpu::BindGroup>,
+            instances: Range<usize>,
+        }
+        let batches_with_instance_buffers = {
+            let mut vec = Vec::new();
+            for batch in self.batches.iter().rev().flatten() {
+                let instance_buffer = batch.instance_buffer();
+                let instances = batch.drawn_instances();
+                let translation_bind_groups = batch
+                    .draw_uniforms()
+                    .into_iter()
+                    .map(|uniform| self.create_translation_bind_group(uniform, batch.label()))
+                    .collect();
+                vec.push(BatchInfo {
+                    batch,
+                    instance_buffer,
+                    translation_bind_groups,
+                    instances,
+                });
+            }
+            vec
+        };
+        let layer_sprites: SpriteGroups = self
+            .layers
+            .iter()
+            .filter(|layer| layer.visible())
+            .flat_map(|layer| layer.groups())
+            .collect();
+        let immediate_sprites = std::mem::take(&mut self.immediate_sprites);
+        let immediate_with_instance_buffers: Vec<_> = layer_sprites
+            .iter()
+            .chain(&immediate_sprites)
+            .map(|(sheet, instances)| {
+                let instance_buffer = create_buffer_checked(
+                    &self.device,
+                    bytemuck::cast_slice(instances),
+                    wgpu::BufferUsage::VERTEX,
+                );
+                (sheet, instance_buffer, instances.len())
+            })
+            .collect();
+        let device = &self.device;
+        let scratch = &mut self.multi_sheet_scratch;
+        let multi_with_instance_buffers: Vec<_> = self
+            .multi_sheet_batches
+            .iter()
+            .filter(|batch| batch.visible() && !batch.is_empty())
+            .map(|batch| {
+                // The buffer copies the instances, so scratch can be reused right away
+                scratch.clear();
+                scratch.extend(
+                    batch
+                        .instances()
+                        .iter()
+                        .filter(|instance| !instance.draws_nothing()),
+                );
+                let instance_buffer = create_buffer_checked(
+                    device,
+                    bytemuck::cast_slice(scratch),
+                    wgpu::BufferUsage::VERTEX,
+                );
+                (batch, instance_buffer, scratch.len())
+            })
+            .collect();
+        let identity_translation_bind_group =
+            self.create_translation_bind_group(BatchUniform::IDENTITY, None);
+        let mut stats = RenderStats {
+            buffers_allocated: 1
+                + immediate_with_instance_buffers.len()
+                + multi_with_instance_buffers.len()
+                + batches_with_instance_buffers
+                    .iter()
+                    .map(|info| info.translation_bind_groups.len())
+                    .sum::<usize>(),
+            ..RenderStats::default()
+        };
+        let scale_uniform_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.scale_uniform_bind_group_layout,
+            bindings: &[wgpu::Binding {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer {
+                    buffer: &self.scale_uniform_buffer,
+                    range: 0..std::mem::size_of::<ScaleUniform>() as wgpu::BufferAddress,
+                },
+            }],
+            label: Some("default_scale_uniform_bind_group"),
+        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Render Encoder"),
+            });
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                    attachment: view,
+                    resolve_target: None,
+                    load_op: if self.clear_on_render {
+                        wgpu::LoadOp::Clear
+                    } else {
+                        wgpu::LoadOp::Load
+                    },
+                    store_op: wgpu::StoreOp::Store,
+                    clear_color: wgpu::Color {
+                        r: 0.0,
+                        g: 0.0,
+                        b: 0.0,
+                        a: 0.0,
+                    },
+                }],
+                depth_stencil_attachment: match &self.depth_texture {
+                    Some(depth_texture) if self.depth_enabled => {
+                        Some(wgpu::RenderPassDepthStencilAttachmentDescriptor {
+                            attachment: &depth_texture.view,
+                            depth_load_op: wgpu::LoadOp::Clear,
+                            depth_store_op: wgpu::StoreOp::Store,
+                            clear_depth: 1.0,
+                            stencil_load_op: wgpu::LoadOp::Clear,
+                            stencil_store_op: wgpu::StoreOp::Store,
+                            clear_stencil: 0,
+                        })
+                    }
+                    _ => None,
+                },
+            });
+            render_pass.set_index_buffer(&self.index_buffer, 0, 0);
+            if let Some(viewport) = self.viewport {
+                let [x1, y1] = viewport.upper_left();
+                let [x2, y2] = viewport.lower_right();
+                let [sx, sy] = self.scale;
+                let [wx1, wy1] = self.scene_to_window_fraction([x1 / sx, y1 / sy]);
+                let [wx2, wy2] = self.scene_to_window_fraction([x2 / sx, y2 / sy]);
+                let [width, height] = [width as f32, height as f32];
+                render_pass.set_viewport(
+                    wx1.min(wx2) * width,
+                    wy1.min(wy2) * height,
+                    (wx2 - wx1).abs() * width,
+                    (wy2 - wy1).abs() * height,
+                    0.0,
+                    1.0,
+                );
+            }
+            let pipelines = self.debug_pipelines.as_ref().unwrap_or(&self.pipelines);
+            if !multi_with_instance_buffers.is_empty() {
+                render_pass.set_pipeline(pipelines.multi(self.depth_enabled));
+                for (batch, instance_buffer, instance_len) in &multi_with_instance_buffers {
+                    render_pass.set_bind_group(0, batch.bind_group(), &[]);
+                    render_pass.set_bind_group(1, &scale_uniform_bind_group, &[]);
+                    render_pass.set_bind_group(2, &identity_translation_bind_group, &[]);
+                    render_pass.set_vertex_buffer(0, instance_buffer, 0, 0);
+                    render_pass.draw_indexed(
+                        0..QUAD_INDICES.len() as u32,
+                        0,
+                        0..*instance_len as u32,
+                    );
+                    stats.batches_drawn += 1;
+                    stats.draw_calls += 1;
+                    stats.total_instances += instance_len;
+                }
+            }
+            render_pass.set_pipeline(pipelines.sprite(self.depth_enabled));
+            for (sheet, instance_buffer, instance_len) in &immediate_with_instance_buffers {
+                render_pass.set_bind_group(0, sheet.bind_group(), &[]);
+                render_pass.set_bind_group(1, &scale_uniform_bind_group, &[]);
+                render_pass.set_bind_group(2, &identity_translation_bind_group, &[]);
+                render_pass.set_vertex_buffer(0, instance_buffer, 0, 0);
+                render_pass.draw_indexed(0..QUAD_INDICES.len() as u32, 0, 0..*instance_len as u32);
+                stats.batches_drawn += 1;
+                stats.draw_calls += 1;
+                stats.total_instances += instance_len;
+            }
+            for info in &batches_with_instance_buffers {
+                let batch = info.batch;
+                let instance_buffer = &info.instance_buffer;
+                let instances = &info.instances;
+                match batch.palette() {
+                    Some(palette) => {
+                        render_pass.set_pipeline(pipelines.palette(self.depth_enabled));
+                        // Palette entries are distinct colors, never blended
+                        render_pass.set_bind_group(
+                            3,
+                            palette.bind_group_with_filter(Filter::Nearest),
+                            &[],
+                        );
+                    }
+                    None => render_pass.set_pipeline(pipelines.sprite(self.depth_enabled)),
+                }
+                let bind_group = batch.sheet().bind_group_with_filter(batch.filter());
+                render_pass.set_bind_group(0, bind_group, &[]);
+                render_pass.set_bind_group(1, &scale_uniform_bind_group, &[]);
+                render_pass.set_vertex_buffer(0, instance_buffer, 0, 0);
+                for translation_bind_group in &info.translation_bind_groups {
+                    render_pass.set_bind_group(2, translation_bind_group, &[]);
+                    render_pass.draw_indexed(
+                        0..QUAD_INDICES.len() as u32,
+                        0,
+                        instances.start as u32..instances.end as u32,
+                    );
+                    stats.draw_calls += 1;
+                    stats.total_instances += instances.len();
+                }
+                stats.batches_drawn += 1;
+            }
+        }
+
+        self.queue.submit(&[encoder.finish()]);
+        for batch in self.batches.iter_mut().flatten() {
+            batch.clear_frame_transform();
+        }
+        self.last_frame_stats = stats;
+        Ok(())
+    }
+
+    /// True if rendering would leave the target unchanged, because nothing
+    /// would be drawn and the target wouldn't be cleared
+    pub(super) fn can_skip_render(&self) -> bool {
+        !self.clear_on_render
+            && self.batches.iter().flatten().all(|batch| batch.len() == 0)
+            && self
+                .layers
+                .iter()
+                .all(|layer| !layer.visible() || layer.is_empty())
+            && self.immediate_sprites.is_empty()
+            && self
+                .multi_sheet_batches
+                .iter()
+                .all(|batch| !batch.visible() || batch.is_empty())
+    }
+
+    /// Drops everything that only lasts for a single frame, as rendering would,
+    /// but without drawing anything (see set_render_when_hidden)
+    pub(super) fn skip_hidden_frame(&mut self) {
+        self.immediate_sprites.clear();
+        for batch in self.batches.iter_mut().flatten() {
+            batch.clear_frame_transform();
+        }
+        self.last_frame_stats = RenderStats::default();
+    }
+
+    /// Sleeps for whatever is left of the current frame's time budget
+    /// (if a target fps is set), then starts timing the next frame
+    pub(super) fn pace_frame(&mut self) {
+        if let (Some(fps), Some(last)) = (self.target_fps, self.last_frame_time) {
+            let budget = Duration::from_secs_f32(1.0 / fps);
+            let elapsed = last.elapsed();
+            if elapsed < budget {
+                std::thread::sleep(budget - elapsed);
+            }
+        }
+        self.last_frame_time = Some(Instant::now());
+    }
+
+    /// Converts a fraction of the way across the scene to a fraction of the
+    /// way across the window with the current settings.
+    /// See the free function scene_to_window_fraction
+    pub(super) fn scene_to_window_fraction(&self, p: [f32; 2]) -> [f32; 2] {
+        scene_to_window_fraction(self.orientation, self.y_axis, p)
+    }
+
+    /// Inverse of scene_to_window_fraction
+    pub(super) fn window_to_scene_fraction(&self, p: [f32; 2]) -> [f32; 2] {
+        window_to_scene_fraction(self.orientation, self.y_axis, p)
+    }
+
+    /// Recreates the global uniform buffer from the current settings.
+    /// Needs to be called whenever any of them change
+    pub(super) fn update_scale_uniform(&mut self) {
+        let uniform = ScaleUniform {
+            scale: self.scale,
+            framebuffer_size: [self.sc_desc.width as f32, self.sc_desc.height as f32],
+            pixel_snapping: if self.pixel_snapping { 1.0 } else { 0.0 },
+            y_up: if self.y_axis == YAxis::Up { 1.0 } else { 0.0 },
+            quarter_turns: self.orientation.quarter_turns() as f32,
+        };
+        self.scale_uniform_buffer = create_buffer_checked(
+            &self.device,
+            bytemuck::bytes_of(&uniform),
+            wgpu::BufferUsage::UNIFORM,
+        );
+    }
+
+    /// Makes sure that the depth texture exists and has the given size,
+    /// (re)creating it if needed
+    pub(super) fn ensure_depth_texture(&mut self, width: u32, height: u32) {
+        if let Some(depth_texture) = &self.depth_texture {
+            if depth_texture.size == [width, height] {
+                return;
+            }
+        }
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth: 1,
+            },
+            array_layer_count: 1,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: DEPTH_FORMAT,
+            usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+            label: Some("depth_texture"),
+        });
+        let view = texture.create_default_view();
+        self.depth_texture = Some(DepthTexture {
+            _texture: texture,
+            view,
+            size: [width, height],
+        });
+    }
+
+    /// `label` names the bind group in GPU debugging tools (see set_batch_label)
+    pub(super) fn create_translation_bind_group(
+        &self,
+        uniform: BatchUniform,
+        label: Option<&str>,
+    ) -> wgpu::BindGroup {
+        let translation_buffer = create_buffer_checked(
+            &self.device,
+            bytemuck::bytes_of(&uniform),
+            wgpu::BufferUsage::UNIFORM,
+        );
+        self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.translation_uniform_bind_group_layout,
+            bindings: &[wgpu::Binding {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer {
+                    buffer: &translation_buffer,
+                    range: 0..std::mem::size_of::<BatchUniform>() as wgpu::BufferAddress,
+                },
+            }],
+            label: Some(label.unwrap_or("per_batch_scale_uniform_bind_group")),
+        })
+    }
+
     pub(super) fn pixel_batch(&mut self) -> Result<&mut Batch> {
         if self.batches[BATCH_SLOT_PIXEL].is_none() {
-            let [width, height] = self.scale();
-            let width = width as usize;
-            let height = height as usize;
+            let [width, height] = pixel_grid_dim(self.scale());
             let mut descs = vec![];
             for y in 0..height {
                 let y = y as f32;
@@ -178,16 +637,272 @@ impl Graphics2D {
                 }
             }
             let sheet = Sheet::from_color(self, [1.0, 1.0, 1.0])?;
-            let batch = Batch::new(self, sheet, 1, 1, &descs);
+            let mut batch = Batch::new(self, sheet, 1, 1, &descs);
+            batch.set_label("a2d_pixel_batch");
             self.batches[BATCH_SLOT_PIXEL] = Some(batch);
         }
         Ok(self.batches[BATCH_SLOT_PIXEL].as_mut().unwrap())
     }
 
+    pub(super) fn white_sheet(&mut self) -> Result<Arc<Sheet>> {
+        if self.white_sheet.is_none() {
+            self.white_sheet = Some(Sheet::from_color(self, [1.0, 1.0, 1.0])?);
+        }
+        Ok(self.white_sheet.clone().unwrap())
+    }
+
+    pub(super) fn placeholder_sheet(&mut self) -> Result<Arc<Sheet>> {
+        /// Width and height of the sheet, in squares of the checkerboard
+        const NSQUARES: u32 = 8;
+        /// Width and height of each square in pixels, large enough
+        /// that the edges stay crisp when the sheet is scaled up
+        const SQUARE_SIZE: u32 = 8;
+        if self.placeholder_sheet.is_none() {
+            let size = NSQUARES * SQUARE_SIZE;
+            let palette = [[1.0, 0.0, 1.0], [0.0, 0.0, 0.0]];
+            let mut colors = Vec::new();
+            for y in 0..size {
+                for x in 0..size {
+                    let parity = (x / SQUARE_SIZE + y / SQUARE_SIZE) % 2;
+                    colors.push(palette[parity as usize]);
+                }
+            }
+            self.placeholder_sheet = Some(Sheet::from_colors(self, size, size, colors)?);
+        }
+        Ok(self.placeholder_sheet.clone().unwrap())
+    }
+
+    pub(super) fn disk_sheet(&mut self) -> Result<Arc<Sheet>> {
+        if self.disk_sheet.is_none() {
+            let size = line::DISK_SHEET_SIZE;
+            let pixels = line::disk_pixels();
+            self.disk_sheet = Some(Sheet::from_rgba_bytes(self, size, size, pixels)?);
+        }
+        Ok(self.disk_sheet.clone().unwrap())
+    }
+
+    /// Queues an instance to be drawn on the next render
+    /// (see `draw_sprite`)
+    pub(super) fn queue_instance(&mut self, sheet: &Arc<Sheet>, instance: Instance) {
+        // Queued sprites can't be looked up again, so ones that wouldn't
+        // draw anything can be dropped right away instead of being uploaded
+        if !instance.draws_nothing() {
+            push_grouped(&mut self.immediate_sprites, sheet, instance);
+        }
+        self.dirty = true;
+    }
+
+    /// Initializes the text grid with the default number of columns
+    /// if it hasn't been initialized yet, or recreates it (with the same
+    /// number of columns) if its batches were removed from their slots
+    pub(super) fn ensure_text_grid(&mut self) -> Result<TextGridDim> {
+        let batches_present =
+            self.batches[BATCH_SLOT_TEXT].is_some() && self.batches[BATCH_SLOT_TEXT_BG].is_some();
+        match text_grid_init_ncols(self.text_grid_dim, batches_present) {
+            Some(ncols) => self.init_text_grid(ncols),
+            None => Ok(self.text_grid_dim.unwrap()),
+        }
+    }
+
+    /// Checks that every sheet that will be drawn on the next render
+    /// is compatible with the render pipeline, so that we return an error
+    /// instead of failing wgpu's validation
+    pub(super) fn check_sheet_layouts(&self) -> Result<()> {
+        let sheets = self
+            .batches
+            .iter()
+            .flatten()
+            .flat_map(|batch| std::iter::once(batch.sheet()).chain(batch.palette()))
+            .chain(self.layers.iter().flat_map(|layer| layer.sheets()))
+            .chain(
+                self.immediate_sprites
+                    .iter()
+                    .map(|(sheet, _)| sheet.as_ref()),
+            );
+        for sheet in sheets {
+            if sheet.layout_tag() != self.texture_layout_tag {
+                err!(
+                    "Tried to draw a Sheet that is incompatible with this Graphics2D's \
+                    render pipeline (was it created with a different Graphics2D?)"
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that nothing that will be drawn on the next render
+    /// exceeds max_instances_per_batch (if set)
+    pub(super) fn check_instance_counts(&self) -> Result<()> {
+        let max = match self.max_instances_per_batch {
+            Some(max) => max,
+            None => return Ok(()),
+        };
+        let batch_counts = self.batches.iter().flatten().map(|batch| batch.len());
+        let layer_counts = self
+            .layers
+            .iter()
+            .filter(|layer| layer.visible())
+            .flat_map(|layer| layer.groups())
+            .map(|(_, instances)| instances.len());
+        let immediate_counts = self
+            .immediate_sprites
+            .iter()
+            .map(|(_, instances)| instances.len());
+        let multi_counts = self
+            .multi_sheet_batches
+            .iter()
+            .filter(|batch| batch.visible())
+            .map(|batch| batch.len());
+        for count in batch_counts
+            .chain(layer_counts)
+            .chain(immediate_counts)
+            .chain(multi_counts)
+        {
+            if count > max {
+                err!(
+                    "Tried to draw {} instances in a single batch, but the limit is {} \
+                    (see Graphics2D::set_max_instances_per_batch)",
+                    count,
+                    max
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Redraws every row of the text grid from the scrollback
+    /// (see push_text_line and scroll_text)
+    pub(super) fn redraw_text_scrollback(&mut self) -> Result<()> {
+        let TextGridDim { nrows, ncols } = self.ensure_text_grid()?;
+        let len = self.text_scrollback.len();
+        for row in 0..nrows {
+            // index of the scrollback row to show in this grid row
+            let index = (len + row).checked_sub(nrows + self.text_scroll);
+            let line = match index {
+                Some(index) => self.text_scrollback[index].clone(),
+                None => String::new(),
+            };
+            let padding = ncols.saturating_sub(line.chars().count());
+            let padded = line + &" ".repeat(padding);
+            self.draw_text(row, 0, &padded)?;
+        }
+        Ok(())
+    }
+
+    /// Width of a character cell of the text grid in the coordinates set by
+    /// set_scale. The text grid's batches must exist
+    pub(super) fn text_grid_scene_char_width(&self) -> f32 {
+        let batch = self.batches[BATCH_SLOT_TEXT].as_ref().unwrap();
+        self.text_grid_base_char_width * batch.scale()[0]
+    }
+
+    /// Scales the text grid's batches so that each character cell is
+    /// `char_width` wide in the coordinates set by set_scale.
+    /// The text grid's batches must exist
+    pub(super) fn set_text_grid_scene_char_width(&mut self, char_width: f32) {
+        let factor = char_width / self.text_grid_base_char_width;
+        for &slot in &[BATCH_SLOT_TEXT, BATCH_SLOT_TEXT_BG] {
+            self.batches[slot]
+                .as_mut()
+                .unwrap()
+                .set_scale([factor, factor]);
+        }
+        self.dirty = true;
+    }
+
     pub(super) fn text_batch(&mut self) -> Result<&mut Batch> {
+        self.ensure_text_grid()?;
         Ok(self.batches[BATCH_SLOT_TEXT].as_mut().unwrap())
     }
 
+    /// Recreates all pipelines from the current shaders and settings.
+    /// Needs to be called whenever any of them change
+    pub(super) fn rebuild_pipelines(&mut self) {
+        let options = PipelineOptions {
+            format: self.sc_desc.format,
+            blend: Blend::Alpha,
+            depth_bias: self.depth_bias,
+            depth_bias_slope_scale: self.depth_bias_slope_scale,
+        };
+        let modules = &self.shader_modules;
+        self.pipelines = Pipelines::new(
+            &self.device,
+            &self.pipeline_layouts,
+            &modules.vs,
+            &modules.fs,
+            &modules.multi_fs,
+            &modules.palette_fs,
+            options,
+        );
+        let debug_options = PipelineOptions {
+            blend: match self.debug_mode {
+                DebugMode::ShowOverdraw => Blend::Additive,
+                DebugMode::Normal | DebugMode::ShowUv => Blend::Alpha,
+            },
+            ..options
+        };
+        // The debug shaders don't sample any sheets, so the same one
+        // works for MultiSheetBatches and palettes too
+        self.debug_pipelines = self.debug_fs_module.as_ref().map(|fs_module| {
+            Pipelines::new(
+                &self.device,
+                &self.pipeline_layouts,
+                &modules.vs,
+                fs_module,
+                fs_module,
+                fs_module,
+                debug_options,
+            )
+        });
+        self.dirty = true;
+    }
+
+    /// Passes the text shadow and outline on to the text batch (if it exists)
+    /// as underlays, with the outline drawn above the shadow
+    pub(super) fn update_text_underlays(&mut self) {
+        let mut underlays = vec![];
+        if let Some((offset, color)) = self.text_shadow {
+            underlays.push((offset, color));
+        }
+        if let Some((width, color)) = self.text_outline {
+            for &dx in &[-width, 0.0, width] {
+                for &dy in &[-width, 0.0, width] {
+                    if dx != 0.0 || dy != 0.0 {
+                        underlays.push(([dx, dy], color));
+                    }
+                }
+            }
+        }
+        if let Some(batch) = self.batches[BATCH_SLOT_TEXT].as_mut() {
+            batch.set_underlays(underlays);
+        }
+        self.dirty = true;
+    }
+
+    pub(super) fn text_bg_batch(&mut self) -> Result<&mut Batch> {
+        self.ensure_text_grid()?;
+        Ok(self.batches[BATCH_SLOT_TEXT_BG].as_mut().unwrap())
+    }
+
+    /// Runs `f`, passing any error it returns to the error handler
+    /// (see set_error_handler) before returning it
+    pub(super) fn reporting<T, F>(&mut self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Self) -> Result<T>,
+    {
+        let result = f(self);
+        self.report(result)
+    }
+
+    /// Passes the error in `result` (if any) to the error handler
+    /// (see set_error_handler) and returns `result` as is
+    pub(super) fn report<T>(&self, result: Result<T>) -> Result<T> {
+        if let (Err(error), Some(handler)) = (&result, &self.error_handler) {
+            handler(&format!("{}", error));
+        }
+        result
+    }
+
     pub(super) fn ensure_polling(&mut self) -> Result<()> {
         if self.poll_thread.is_none() {
             let device = self.device.clone();
@@ -221,3 +936,167 @@ impl Graphics2D {
         Ok(())
     }
 }
+
+/// Converts a fraction of the way across the scene (i.e. a position in
+/// the coordinates set by set_scale, divided by the scale) to a fraction
+/// of the way across the window, taking the y axis and orientation into account.
+/// This mirrors what the vertex shader does
+fn scene_to_window_fraction(orientation: Orientation, y_axis: YAxis, [x, y]: [f32; 2]) -> [f32; 2] {
+    let y = match y_axis {
+        YAxis::Down => y,
+        YAxis::Up => 1.0 - y,
+    };
+    match orientation {
+        Orientation::Normal => [x, y],
+        Orientation::Rotate90 => [1.0 - y, x],
+        Orientation::Rotate180 => [1.0 - x, 1.0 - y],
+        Orientation::Rotate270 => [y, 1.0 - x],
+    }
+}
+
+/// Inverse of scene_to_window_fraction
+fn window_to_scene_fraction(orientation: Orientation, y_axis: YAxis, [x, y]: [f32; 2]) -> [f32; 2] {
+    let [x, y] = match orientation {
+        Orientation::Normal => [x, y],
+        Orientation::Rotate90 => [y, 1.0 - x],
+        Orientation::Rotate180 => [1.0 - x, 1.0 - y],
+        Orientation::Rotate270 => [1.0 - y, x],
+    };
+    match y_axis {
+        YAxis::Down => [x, y],
+        YAxis::Up => [x, 1.0 - y],
+    }
+}
+
+/// The number of columns the text grid has to be (re)initialized with
+/// before it's used, or None if it's ready as is (see ensure_text_grid)
+fn text_grid_init_ncols(dim: Option<TextGridDim>, batches_present: bool) -> Option<usize> {
+    match dim {
+        Some(_) if batches_present => None,
+        Some(dim) => Some(dim.ncols),
+        None => Some(DEFAULT_TEXT_NCOLS),
+    }
+}
+
+/// The [width, height] of the pixel batch's grid for the given scale,
+/// i.e. one sprite per whole unit of the drawing area
+pub(super) fn pixel_grid_dim(scale: [f32; 2]) -> [usize; 2] {
+    [scale[0] as usize, scale[1] as usize]
+}
+
+/// Whether the pixel batch built for `old_scale` has to be regenerated
+/// after the scale changes to `new_scale`
+pub(super) fn pixel_grid_is_stale(old_scale: [f32; 2], new_scale: [f32; 2]) -> bool {
+    pixel_grid_dim(old_scale) != pixel_grid_dim(new_scale)
+}
+
+/// Creates a buffer initialized with `data`.
+///
+/// wgpu doesn't allow zero-sized buffers, so empty data (e.g. a batch or
+/// group with no sprites) gets a small zeroed buffer instead. Callers should
+/// keep track of the actual length themselves rather than rely on the size
+/// of the buffer.
+pub(super) fn create_buffer_checked(
+    device: &wgpu::Device,
+    data: &[u8],
+    usage: wgpu::BufferUsage,
+) -> wgpu::Buffer {
+    if data.is_empty() {
+        device.create_buffer_with_data(&[0; EMPTY_BUFFER_SIZE], usage)
+    } else {
+        device.create_buffer_with_data(data, usage)
+    }
+}
+
+/// Size in bytes of the buffer created in place of an empty one.
+/// Buffer copies in wgpu need to be a multiple of 4 bytes
+const EMPTY_BUFFER_SIZE: usize = 4;
+
+impl Drop for Graphics2D {
+    /// Stops the poll thread and waits for all submitted GPU work to finish,
+    /// so that work in flight (e.g. uploads) isn't cut off on shutdown
+    fn drop(&mut self) {
+        if let Some((thread, sender)) = self.poll_thread.take() {
+            // If sending fails, the thread has already stopped
+            let _ = sender.send(());
+            let _ = thread.join();
+        }
+        self.device.poll(wgpu::Maintain::Wait);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIENTATIONS: [Orientation; 4] = [
+        Orientation::Normal,
+        Orientation::Rotate90,
+        Orientation::Rotate180,
+        Orientation::Rotate270,
+    ];
+
+    #[test]
+    fn graphics2d_is_send() {
+        fn is_send<T: Send>() {}
+        is_send::<Graphics2D>();
+    }
+
+    #[test]
+    fn window_fraction_round_trip() {
+        let points = [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0], [0.25, 0.75]];
+        for &orientation in &ORIENTATIONS {
+            for &y_axis in &[YAxis::Down, YAxis::Up] {
+                for &p in &points {
+                    let window = scene_to_window_fraction(orientation, y_axis, p);
+                    assert_eq!(
+                        window_to_scene_fraction(orientation, y_axis, window),
+                        p,
+                        "{:?} {:?}",
+                        orientation,
+                        y_axis,
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn window_fraction_rotate90() {
+        // Rotating clockwise moves the scene's top-left corner
+        // to the window's top-right corner
+        let top_left = [0.0, 0.0];
+        let window = scene_to_window_fraction(Orientation::Rotate90, YAxis::Down, top_left);
+        assert_eq!(window, [1.0, 0.0]);
+
+        // With the y axis pointing up, the top of the scene is at y = 1
+        let top_left = [0.0, 1.0];
+        let window = scene_to_window_fraction(Orientation::Rotate90, YAxis::Up, top_left);
+        assert_eq!(window, [1.0, 0.0]);
+    }
+
+    #[test]
+    fn text_grid_initializes_lazily() {
+        // A fresh Graphics2D gets the default grid on the first text call
+        assert_eq!(text_grid_init_ncols(None, false), Some(DEFAULT_TEXT_NCOLS));
+
+        // If its batches were removed, the grid keeps its number of columns
+        let dim = TextGridDim {
+            nrows: 30,
+            ncols: 40,
+        };
+        assert_eq!(text_grid_init_ncols(Some(dim), false), Some(40));
+        assert_eq!(text_grid_init_ncols(Some(dim), true), None);
+    }
+
+    #[test]
+    fn pixel_grid_regenerates_on_scale_change() {
+        assert!(pixel_grid_is_stale([800.0, 600.0], [400.0, 300.0]));
+        assert!(pixel_grid_is_stale([800.0, 600.0], [800.0, 601.0]));
+        assert!(!pixel_grid_is_stale([800.0, 600.0], [800.0, 600.0]));
+
+        // Fractional parts don't change the number of pixels
+        assert!(!pixel_grid_is_stale([800.0, 600.0], [800.5, 600.25]));
+        assert_eq!(pixel_grid_dim([800.5, 600.25]), [800, 600]);
+    }
+}
@@ -3,8 +3,9 @@ use super::*;
 /// Instance data.
 /// Data passed to the GPU per sprite in a sprite batch.
 #[repr(C)]
-#[derive(Copy, Clone, Debug)]
-pub(super) struct Instance {
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Instance {
     /// [x, y] representing upper-left corner of the rectangle cropped from the source.
     /// Coordinates are between 0 and 1.
     /// Upper left corner is the origin [0, 0].
@@ -27,6 +28,7 @@ pub(super) struct Instance {
 
     /// clockwise rotation in radians.
     /// around the center of the rectangle after moving to the destination rectangle.
+    /// Always normalized to be in [0, 2π)
     rotate: f32,
 
     /// Multiplied by the texture color per-fragment to get the final color returned
@@ -34,12 +36,81 @@ pub(super) struct Instance {
     ///
     /// Defaults to [1.0, 1.0, 1.0, 1.0], so that the color remains unchanged
     color_factor: [f32; 4],
+
+    /// Depth of the sprite, between 0 (front) and 1 (back).
+    /// Only used when depth testing is enabled (see Graphics2D::enable_depth)
+    ///
+    /// Defaults to 0.0
+    #[cfg_attr(feature = "serde", serde(default))]
+    z: f32,
+
+    /// Which of the sheets of a MultiSheetBatch to sample from.
+    /// Ignored everywhere else
+    ///
+    /// Defaults to 0.0
+    #[cfg_attr(feature = "serde", serde(default))]
+    sheet_index: f32,
+
+    /// Colors multiplied with color_factor at each corner of the destination
+    /// rectangle, and interpolated in between: upper-left, upper-right,
+    /// lower-right, then lower-left
+    ///
+    /// Defaults to white everywhere, so that color_factor applies as is
+    #[cfg_attr(feature = "serde", serde(default = "white_corners"))]
+    corner_colors: [[f32; 4]; 4],
+
+    /// Added to the texture coordinates after scaling them by uv_scale,
+    /// e.g. to scroll the texture within the sprite
+    ///
+    /// Defaults to [0.0, 0.0]
+    #[cfg_attr(feature = "serde", serde(default))]
+    uv_offset: [f32; 2],
+
+    /// Multiplied with the texture coordinates sampled from the sheet
+    ///
+    /// Defaults to [1.0, 1.0]
+    #[cfg_attr(feature = "serde", serde(default = "unit_uv_scale"))]
+    uv_scale: [f32; 2],
+}
+
+#[cfg_attr(not(feature = "serde"), allow(dead_code))]
+fn white_corners() -> [[f32; 4]; 4] {
+    [[1.0, 1.0, 1.0, 1.0]; 4]
+}
+
+#[cfg_attr(not(feature = "serde"), allow(dead_code))]
+fn unit_uv_scale() -> [f32; 2] {
+    [1.0, 1.0]
 }
 
 unsafe impl bytemuck::Pod for Instance {}
 unsafe impl bytemuck::Zeroable for Instance {}
 
-const FLOAT_SIZE: wgpu::BufferAddress = std::mem::size_of::<f32>() as wgpu::BufferAddress;
+/// One field of the instance data as seen by the vertex shader
+/// (see `Instance::ATTRIBUTES`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InstanceAttribute {
+    /// Offset in bytes from the start of the instance
+    pub offset: usize,
+
+    /// The `location` of the corresponding input in the vertex shader
+    pub shader_location: u32,
+
+    /// Number of f32 values in the attribute (e.g. 2 for a vec2)
+    pub components: usize,
+}
+
+const fn attribute(
+    offset_in_floats: usize,
+    shader_location: u32,
+    components: usize,
+) -> InstanceAttribute {
+    InstanceAttribute {
+        offset: offset_in_floats * std::mem::size_of::<f32>(),
+        shader_location,
+        components,
+    }
+}
 
 #[allow(dead_code)]
 impl Instance {
@@ -49,8 +120,39 @@ impl Instance {
             dest: [0.0, 0.0, 1.0, 1.0].into(),
             rotate: 0.0,
             color_factor: [1.0, 1.0, 1.0, 1.0],
+            z: 0.0,
         }
     }
+    /// Creates an instance drawing the `src` region of a sheet
+    /// (coordinates between 0 and 1) to `dst`, rotated clockwise
+    /// `rotate` radians around the center of `dst`
+    pub fn from_rects<R1: Into<Rect>, R2: Into<Rect>>(dst: R1, src: R2, rotate: f32) -> Instance {
+        Self::new(src, dst, rotate, [1.0, 1.0, 1.0, 1.0])
+    }
+
+    /// Same as `from_rects` without a rotation, but with the rects wrapped
+    /// so that they can't be passed in the wrong order by accident, e.g.
+    /// `Instance::place(Dst([0.0, 0.0, 32.0, 32.0].into()), Src(frame))`
+    pub fn place(dst: Dst, src: Src) -> Instance {
+        Self::new(src.0, dst.0, 0.0, [1.0, 1.0, 1.0, 1.0])
+    }
+
+    /// Creates an instance drawing the entire source to a rectangle
+    /// of the given [width, height] whose `anchor` point is at `position`.
+    /// Panics if the rectangle would be degenerate
+    pub fn from_anchor(
+        anchor: Anchor,
+        position: [f32; 2],
+        size: [f32; 2],
+        rotate: f32,
+    ) -> Instance {
+        let dest = match Rect::from_anchor(anchor, position, size) {
+            Some(rect) => rect,
+            None => panic!("Tried to construct degenerate a2d Rect"),
+        };
+        Self::builder().dest(dest).rotate(rotate).build()
+    }
+
     fn new<R1: Into<Rect>, R2: Into<Rect>>(
         src: R1,
         dest: R2,
@@ -64,8 +166,13 @@ impl Instance {
             src_lr: src.lower_right(),
             dst_ul: dest.upper_left(),
             dst_lr: dest.lower_right(),
-            rotate,
+            rotate: normalize_angle(rotate),
             color_factor,
+            z: 0.0,
+            sheet_index: 0.0,
+            corner_colors: white_corners(),
+            uv_offset: [0.0, 0.0],
+            uv_scale: unit_uv_scale(),
         }
     }
 
@@ -79,6 +186,40 @@ impl Instance {
         self.src_lr = rect.lower_right();
     }
 
+    /// Sets src so that the whole sheet is repeated `repeat` times across and
+    /// down the sprite, starting `offset` (as a fraction of the sheet) into it.
+    /// Scrolling the offset over time makes the tiles scroll within the sprite.
+    ///
+    /// This only tiles if the sheet was created with SheetUsage::repeat.
+    /// Otherwise the edges of the sheet are stretched instead
+    /// (which is caught by a debug assertion when the sprite is drawn).
+    pub fn set_tiled_src(&mut self, offset: [f32; 2], repeat: [f32; 2]) {
+        let [x, y] = offset;
+        let [rx, ry] = repeat;
+        self.src_ul = [x, y];
+        self.src_lr = [x + rx, y + ry];
+    }
+
+    /// True if drawing this instance can't change the output, because it's
+    /// entirely transparent or has no area, so that it can be left out
+    /// of the instances uploaded for a render
+    pub(super) fn draws_nothing(&self) -> bool {
+        self.color_factor[3] == 0.0
+            || self.corner_colors.iter().all(|color| color[3] == 0.0)
+            || self.dst_ul[0] == self.dst_lr[0]
+            || self.dst_ul[1] == self.dst_lr[1]
+    }
+
+    /// True if src is within the bounds of the sheet
+    /// (allowing for a little floating point error)
+    pub(super) fn src_in_sheet(&self) -> bool {
+        const EPSILON: f32 = 1e-4;
+        self.src_ul
+            .iter()
+            .chain(&self.src_lr)
+            .all(|c| *c >= -EPSILON && *c <= 1.0 + EPSILON)
+    }
+
     pub fn dest(&self) -> Rect {
         [self.dst_ul, self.dst_lr].into()
     }
@@ -93,73 +234,257 @@ impl Instance {
         self.rotate
     }
 
+    /// Sets the clockwise rotation in radians.
+    /// The angle is normalized to be in [0, 2π), so that precision isn't
+    /// lost when rotations accumulate over a long time
     pub fn set_rotation(&mut self, rotate: f32) {
-        self.rotate = rotate;
+        self.rotate = normalize_angle(rotate);
+    }
+
+    pub fn rotation_degrees(&self) -> f32 {
+        self.rotate.to_degrees()
+    }
+
+    pub fn set_rotation_degrees(&mut self, degrees: f32) {
+        self.set_rotation(degrees.to_radians());
+    }
+
+    pub fn color_factor(&self) -> Color {
+        self.color_factor.into()
+    }
+
+    /// Maps a point to where it lands on this instance, undoing the rotation.
+    /// Returns [u, v], where [0, 0] is the upper-left corner of the
+    /// destination rectangle and [1, 1] is the lower-right corner.
+    /// (u and v are outside of [0, 1] if the point isn't on this instance)
+    pub fn to_local<P: Into<Point>>(&self, point: P) -> [f32; 2] {
+        let point = point.into();
+        let [x1, y1] = self.dst_ul;
+        let [x2, y2] = self.dst_lr;
+        let [cx, cy] = [(x1 + x2) / 2.0, (y1 + y2) / 2.0];
+        let [dx, dy] = [point.x - cx, point.y - cy];
+
+        // rotate counter-clockwise around the center to undo the clockwise rotation
+        let (sin, cos) = self.rotate.sin_cos();
+        let x = cx + cos * dx + sin * dy;
+        let y = cy - sin * dx + cos * dy;
+        [(x - x1) / (x2 - x1), (y - y1) / (y2 - y1)]
+    }
+
+    /// Returns true if the point is on this instance (taking rotation into account)
+    pub fn contains<P: Into<Point>>(&self, point: P) -> bool {
+        let [u, v] = self.to_local(point);
+        (0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v)
+    }
+
+    /// If the point is on this instance, returns the coordinates of the
+    /// texture (between 0 and 1 across the entire sheet) drawn at that point
+    pub fn uv_at<P: Into<Point>>(&self, point: P) -> Option<[f32; 2]> {
+        let [u, v] = self.to_local(point);
+        if (0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v) {
+            let [sx1, sy1] = self.src_ul;
+            let [sx2, sy2] = self.src_lr;
+            let [ox, oy] = self.uv_offset;
+            let [kx, ky] = self.uv_scale;
+            Some([
+                (sx1 + u * (sx2 - sx1)) * kx + ox,
+                (sy1 + v * (sy2 - sy1)) * ky + oy,
+            ])
+        } else {
+            None
+        }
     }
 
     pub fn set_color_factor<C: Into<Color>>(&mut self, color_factor: C) {
         self.color_factor = color_factor.into().to_array();
     }
 
-    pub(super) fn desc<'a>() -> wgpu::VertexBufferDescriptor<'a> {
-        assert_eq!(
-            std::mem::align_of::<Instance>(),
-            std::mem::align_of::<f32>(),
-        );
-        assert_eq!(
-            std::mem::size_of::<Instance>(),
-            std::mem::size_of::<f32>() * 13,
+    pub fn corner_colors(&self) -> [Color; 4] {
+        let [a, b, c, d] = self.corner_colors;
+        [a.into(), b.into(), c.into(), d.into()]
+    }
+
+    /// Sets a color for each corner of the destination rectangle (upper-left,
+    /// upper-right, lower-right, then lower-left), blended smoothly across
+    /// the sprite, e.g. for gradients drawn with a plain white sheet.
+    ///
+    /// The corner colors are multiplied with the color factor, so setting
+    /// every corner to the same color has the same effect as
+    /// `set_color_factor` (with a white color factor)
+    pub fn set_corner_colors<C: Into<Color>>(&mut self, colors: [C; 4]) {
+        let [a, b, c, d] = colors;
+        self.corner_colors = [
+            a.into().to_array(),
+            b.into().to_array(),
+            c.into().to_array(),
+            d.into().to_array(),
+        ];
+    }
+
+    /// The smallest axis-aligned rect containing the destination
+    /// rect after it has been rotated
+    pub fn bounds(&self) -> Rect {
+        let [cx, cy] = [
+            (self.dst_ul[0] + self.dst_lr[0]) / 2.0,
+            (self.dst_ul[1] + self.dst_lr[1]) / 2.0,
+        ];
+        let [hw, hh] = [
+            (self.dst_lr[0] - self.dst_ul[0]) / 2.0,
+            (self.dst_lr[1] - self.dst_ul[1]) / 2.0,
+        ];
+        let [cos, sin] = [self.rotate.cos().abs(), self.rotate.sin().abs()];
+        let [bw, bh] = [cos * hw + sin * hh, sin * hw + cos * hh];
+        [cx - bw, cy - bh, cx + bw, cy + bh].into()
+    }
+
+    /// Returns true if any part of this instance might be inside `view`
+    /// (e.g. the part of the world currently on screen), for skipping
+    /// instances that definitely won't be seen.
+    ///
+    /// This tests `bounds()` against `view`, so a rotated instance near
+    /// a corner of `view` may count as visible when it actually isn't
+    pub fn is_visible_in(&self, view: &Rect) -> bool {
+        self.bounds().intersects(view)
+    }
+
+    /// Interpolates between `self` (when `t` is 0) and `other` (when `t` is 1).
+    /// The destination rectangle, colors and z are interpolated linearly,
+    /// and the rotation along the shorter way around the circle.
+    /// The source rectangle and sheet index are always those of `self`.
+    /// See the `tween` module
+    pub fn lerp(&self, other: &Instance, t: f32) -> Instance {
+        let mut instance = Instance::new(
+            self.src(),
+            self.dest().lerp(&other.dest(), t),
+            tween::lerp_angle(self.rotate, other.rotate, t),
+            self.color_factor()
+                .lerp(&other.color_factor(), t)
+                .to_array(),
         );
-        use std::mem;
-        wgpu::VertexBufferDescriptor {
-            stride: mem::size_of::<Instance>() as wgpu::BufferAddress,
-            step_mode: wgpu::InputStepMode::Instance,
-            attributes: &[
-                wgpu::VertexAttributeDescriptor {
-                    offset: 0,
-                    shader_location: 0,
-                    format: wgpu::VertexFormat::Float2,
-                },
-                wgpu::VertexAttributeDescriptor {
-                    offset: FLOAT_SIZE * 2,
-                    shader_location: 1,
-                    format: wgpu::VertexFormat::Float2,
-                },
-                wgpu::VertexAttributeDescriptor {
-                    offset: FLOAT_SIZE * (2 + 2),
-                    shader_location: 2,
-                    format: wgpu::VertexFormat::Float2,
-                },
-                wgpu::VertexAttributeDescriptor {
-                    offset: FLOAT_SIZE * (2 + 2 + 2),
-                    shader_location: 3,
-                    format: wgpu::VertexFormat::Float2,
-                },
-                wgpu::VertexAttributeDescriptor {
-                    offset: FLOAT_SIZE * (2 + 2 + 2 + 2),
-                    shader_location: 4,
-                    format: wgpu::VertexFormat::Float,
-                },
-                wgpu::VertexAttributeDescriptor {
-                    offset: FLOAT_SIZE * (2 + 2 + 2 + 2 + 1),
-                    shader_location: 5,
-                    format: wgpu::VertexFormat::Float4,
-                },
-            ],
+        instance.z = tween::lerp(self.z, other.z, t);
+        instance.sheet_index = self.sheet_index;
+        instance.uv_offset = Point::from(self.uv_offset)
+            .lerp(&other.uv_offset.into(), t)
+            .to_array();
+        instance.uv_scale = Point::from(self.uv_scale)
+            .lerp(&other.uv_scale.into(), t)
+            .to_array();
+        for i in 0..4 {
+            let color: Color = self.corner_colors[i].into();
+            instance.corner_colors[i] = color.lerp(&other.corner_colors[i].into(), t).to_array();
         }
+        instance
+    }
+
+    pub fn z(&self) -> f32 {
+        self.z
+    }
+
+    /// Sets the depth of this instance, between 0 (front) and 1 (back).
+    /// Values outside of this range are clamped when drawing
+    pub fn set_z(&mut self, z: f32) {
+        self.z = z;
+    }
+
+    pub fn uv_offset(&self) -> [f32; 2] {
+        self.uv_offset
+    }
+
+    /// Shifts the texture coordinates sampled across the sprite by `offset`
+    /// (in fractions of the sheet) without changing src or dst. Animating this
+    /// scrolls the texture within a stationary sprite (e.g. for a waterfall),
+    /// which wraps around smoothly on sheets created with SheetUsage::repeat
+    pub fn set_uv_offset(&mut self, offset: [f32; 2]) {
+        self.uv_offset = offset;
+    }
+
+    pub fn uv_scale(&self) -> [f32; 2] {
+        self.uv_scale
+    }
+
+    /// Multiplies the texture coordinates sampled across the sprite by `scale`
+    /// (before `uv_offset` is added), without changing src or dst
+    pub fn set_uv_scale(&mut self, scale: [f32; 2]) {
+        self.uv_scale = scale;
+    }
+
+    /// Which sheet of a MultiSheetBatch this instance is drawn from
+    /// (see MultiSheetBatch::push)
+    pub fn sheet_index(&self) -> usize {
+        self.sheet_index as usize
+    }
+
+    pub(super) fn set_sheet_index(&mut self, sheet_index: usize) {
+        self.sheet_index = sheet_index as f32;
+    }
+
+    // ---------------
+    // Advanced: for custom renderers that reuse a2d's instance format
+    // ---------------
+
+    /// Size in bytes of a single instance in a vertex buffer
+    pub const STRIDE: usize = std::mem::size_of::<Instance>();
+
+    /// The layout of the per-instance vertex data that a2d's vertex shader expects
+    /// (all attributes are made up of f32 values).
+    /// Use this to create a matching pipeline in a custom renderer.
+    pub const ATTRIBUTES: [InstanceAttribute; 14] = [
+        attribute(0, 0, 2),                                          // src upper-left
+        attribute(2, 1, 2),                                          // src lower-right
+        attribute(2 + 2, 2, 2),                                      // dst upper-left
+        attribute(2 + 2 + 2, 3, 2),                                  // dst lower-right
+        attribute(2 + 2 + 2 + 2, 4, 1),                              // rotation
+        attribute(2 + 2 + 2 + 2 + 1, 5, 4),                          // color factor
+        attribute(2 + 2 + 2 + 2 + 1 + 4, 6, 1),                      // z
+        attribute(2 + 2 + 2 + 2 + 1 + 4 + 1, 7, 1),                  // sheet index
+        attribute(2 + 2 + 2 + 2 + 1 + 4 + 1 + 1, 8, 4),              // upper-left corner color
+        attribute(2 + 2 + 2 + 2 + 1 + 4 + 1 + 1 + 4, 9, 4),          // upper-right corner color
+        attribute(2 + 2 + 2 + 2 + 1 + 4 + 1 + 1 + 4 * 2, 10, 4),     // lower-right corner color
+        attribute(2 + 2 + 2 + 2 + 1 + 4 + 1 + 1 + 4 * 3, 11, 4),     // lower-left corner color
+        attribute(2 + 2 + 2 + 2 + 1 + 4 + 1 + 1 + 4 * 4, 12, 2),     // uv offset
+        attribute(2 + 2 + 2 + 2 + 1 + 4 + 1 + 1 + 4 * 4 + 2, 13, 2), // uv scale
+    ];
+
+    /// The raw bytes of the given instances, in the layout described by
+    /// `ATTRIBUTES`, ready to be uploaded to a vertex buffer
+    pub fn slice_as_bytes(instances: &[Instance]) -> &[u8] {
+        bytemuck::cast_slice(instances)
     }
 }
 
-pub(super) struct InstanceBuilder {
+/// Where on screen an instance is drawn (see Instance::place)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dst(pub Rect);
+
+/// The region of a sheet an instance is drawn from, with coordinates
+/// between 0 and 1 (see Instance::place)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Src(pub Rect);
+
+/// Returns the equivalent angle in [0, 2π)
+fn normalize_angle(theta: f32) -> f32 {
+    let theta = theta.rem_euclid(std::f32::consts::PI * 2.0);
+    // rem_euclid may round up to exactly 2π for tiny negative angles
+    if theta >= std::f32::consts::PI * 2.0 {
+        0.0
+    } else {
+        theta
+    }
+}
+
+pub struct InstanceBuilder {
     src: Rect,
     dest: Rect,
     rotate: f32,
     color_factor: [f32; 4],
+    z: f32,
 }
 
 impl InstanceBuilder {
     pub fn build(self) -> Instance {
-        Instance::new(self.src, self.dest, self.rotate, self.color_factor)
+        let mut instance = Instance::new(self.src, self.dest, self.rotate, self.color_factor);
+        instance.z = self.z;
+        instance
     }
 
     pub fn src<R: Into<Rect>>(mut self, src: R) -> Self {
@@ -186,6 +511,13 @@ impl InstanceBuilder {
         self.color_factor = color_factor.into().to_array();
         self
     }
+
+    /// Sets the depth, between 0 (front) and 1 (back)
+    /// (see Graphics2D::enable_depth)
+    pub fn z(mut self, z: f32) -> Self {
+        self.z = z;
+        self
+    }
 }
 
 impl From<InstanceBuilder> for Instance {
@@ -193,3 +525,50 @@ impl From<InstanceBuilder> for Instance {
         builder.build()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uv_at_applies_uv_offset_and_scale() {
+        let mut instance = Instance::builder()
+            .src([0.0, 0.0, 0.5, 0.5])
+            .dest([0.0, 0.0, 10.0, 10.0])
+            .build();
+        assert_eq!(instance.uv_at([5.0, 5.0]), Some([0.25, 0.25]));
+
+        instance.set_uv_offset([0.125, 0.25]);
+        assert_eq!(instance.uv_at([5.0, 5.0]), Some([0.375, 0.5]));
+
+        // Like the vertex shader, the scale applies before the offset
+        instance.set_uv_scale([2.0, 4.0]);
+        assert_eq!(instance.uv_at([5.0, 5.0]), Some([0.625, 1.25]));
+        assert_eq!(instance.uv_at([0.0, 0.0]), Some([0.125, 0.25]));
+        assert_eq!(instance.uv_at([20.0, 5.0]), None);
+    }
+
+    #[test]
+    fn is_visible_in_rotated_across_edge() {
+        let view = Rect::from([11.0, 0.0, 100.0, 100.0]);
+        let mut instance = Instance::builder().dest([0.0, 0.0, 10.0, 10.0]).build();
+        assert!(!instance.is_visible_in(&view));
+
+        // Rotated 45 degrees, the corners reach out to 5 + 5 * sqrt(2)
+        instance.set_rotation_degrees(45.0);
+        assert!(instance.is_visible_in(&view));
+    }
+
+    #[test]
+    fn is_visible_in_outside_or_touching() {
+        let view = Rect::from([0.0, 0.0, 100.0, 100.0]);
+        let outside = Instance::builder()
+            .dest([200.0, 200.0, 210.0, 210.0])
+            .build();
+        assert!(!outside.is_visible_in(&view));
+
+        // Sharing only an edge with the view doesn't count
+        let touching = Instance::builder().dest([100.0, 0.0, 110.0, 10.0]).build();
+        assert!(!touching.is_visible_in(&view));
+    }
+}
@@ -0,0 +1,189 @@
+use super::*;
+
+/// Sprites grouped by the sheet they are drawn from, in draw order.
+/// Consecutive sprites from the same sheet share a group (and so a draw call)
+pub(super) type SpriteGroups = Vec<(Arc<Sheet>, Vec<Instance>)>;
+
+pub(super) fn push_grouped(groups: &mut SpriteGroups, sheet: &Arc<Sheet>, instance: Instance) {
+    debug_assert!(
+        sheet.usage().repeat || instance.src_in_sheet(),
+        "src {:?} is outside of a sheet that doesn't repeat, so its edges would be \
+        stretched (create the sheet with SheetUsage::repeat to tile it)",
+        instance.src(),
+    );
+    match groups.last_mut() {
+        Some((last_sheet, instances)) if Arc::ptr_eq(last_sheet, sheet) => {
+            instances.push(instance);
+        }
+        _ => groups.push((sheet.clone(), vec![instance])),
+    }
+}
+
+/// The smallest rect containing every sprite in all of the given layers
+/// (e.g. for zooming to fit all content), or None if they are all empty
+pub fn bounds_of(layers: &[&Layer]) -> Option<Rect> {
+    layers
+        .iter()
+        .filter_map(|layer| layer.bounds())
+        .fold(None, |acc: Option<Rect>, rect| match acc {
+            Some(acc) => Some(acc.union(&rect)),
+            None => Some(rect),
+        })
+}
+
+/// A named group of sprites that is kept across renders
+/// (unlike sprites queued with Graphics2D::draw_sprite).
+///
+/// Layers are drawn in the order they were added with Graphics2D::add_layer,
+/// beneath sprites queued with draw_sprite and all batches
+pub struct Layer {
+    name: String,
+    visible: bool,
+    sprites: Vec<(Arc<Sheet>, Instance)>,
+}
+
+impl Layer {
+    pub(super) fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            visible: true,
+            sprites: vec![],
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Hidden layers keep their sprites, but are skipped when rendering
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    /// Adds a sprite to the end of this layer
+    /// (see Graphics2D::draw_sprite for the meaning of each argument)
+    pub fn add_sprite<R1: Into<Rect>, R2: Into<Rect>>(
+        &mut self,
+        sheet: &Arc<Sheet>,
+        dst: R1,
+        src: R2,
+        rotation: f32,
+    ) {
+        let instance = Instance::builder()
+            .src(src)
+            .dest(dst)
+            .rotate(rotation)
+            .build();
+        self.add_instance(sheet, instance);
+    }
+
+    /// Adds a sprite described by `instance` to the end of this layer
+    pub fn add_instance(&mut self, sheet: &Arc<Sheet>, instance: Instance) {
+        self.sprites.push((sheet.clone(), instance));
+    }
+
+    /// Adds the sprites needed to draw `nine_patch` stretched to `dst`
+    /// to the end of this layer
+    pub fn add_nine_patch<R: Into<Rect>>(&mut self, nine_patch: &NinePatch, dst: R) {
+        for instance in nine_patch.emit(dst) {
+            self.add_instance(nine_patch.sheet(), instance);
+        }
+    }
+
+    /// Keeps only the sprites for which `f` returns true
+    /// (e.g. to drop sprites that have gone off-screen),
+    /// preserving the order of the remaining sprites
+    pub fn retain<F: FnMut(&Instance) -> bool>(&mut self, mut f: F) {
+        self.sprites.retain(|(_, instance)| f(instance));
+    }
+
+    /// Sorts the sprites in this layer (e.g. by depth) with a stable sort.
+    /// Sprites later in the layer are drawn on top of earlier ones
+    pub fn sort_by<F: FnMut(&Instance, &Instance) -> std::cmp::Ordering>(&mut self, mut f: F) {
+        self.sprites.sort_by(|(_, a), (_, b)| f(a, b));
+    }
+
+    /// The sprite at the given index in this layer (in the order they were added,
+    /// unless rearranged with e.g. `sort_by`), or None if it's out of bounds
+    pub fn get(&self, index: usize) -> Option<&Instance> {
+        self.sprites.get(index).map(|(_, instance)| instance)
+    }
+
+    /// Like `get`, but allows changing the sprite in place
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Instance> {
+        self.sprites.get_mut(index).map(|(_, instance)| instance)
+    }
+
+    /// Finds the topmost sprite in this layer under the given point.
+    /// Returns the index of the sprite in the layer along with the
+    /// coordinates of the texture drawn at that point
+    /// (see Instance::uv_at), e.g. to map a click to a texel in a paint tool
+    pub fn pick_uv<P: Into<Point>>(&self, point: P) -> Option<(usize, [f32; 2])> {
+        let point = point.into();
+        self.sprites
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(i, (_, instance))| instance.uv_at(point).map(|uv| (i, uv)))
+    }
+
+    /// Moves all sprites from `other` to the end of this layer, leaving `other` empty.
+    /// Consecutive sprites from the same sheet are drawn together, so merging
+    /// layers that use the same sheets reduces the number of draw calls
+    pub fn append(&mut self, other: &mut Layer) {
+        self.sprites.append(&mut other.sprites);
+    }
+
+    /// Removes all sprites from this layer
+    pub fn clear(&mut self) {
+        self.sprites.clear();
+    }
+
+    /// Frees memory held onto after this layer shrinks (e.g. once a big
+    /// burst of particles has ended). Layers never give memory back on their
+    /// own, so adding sprites again after this may have to reallocate
+    pub fn shrink_to_fit(&mut self) {
+        self.sprites.shrink_to_fit();
+    }
+
+    /// Returns the number of sprites in this layer
+    pub fn len(&self) -> usize {
+        self.sprites.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sprites.is_empty()
+    }
+
+    /// The smallest rect containing every sprite in this layer
+    /// (taking rotation into account), or None if the layer is empty
+    pub fn bounds(&self) -> Option<Rect> {
+        self.sprites
+            .iter()
+            .map(|(_, instance)| instance.bounds())
+            .fold(None, |acc: Option<Rect>, rect| match acc {
+                Some(acc) => Some(acc.union(&rect)),
+                None => Some(rect),
+            })
+    }
+
+    pub(super) fn sheets(&self) -> impl Iterator<Item = &Sheet> {
+        self.sprites.iter().map(|(sheet, _)| sheet.as_ref())
+    }
+
+    /// The sprites to upload for a render, grouped by sheet.
+    /// Sprites that wouldn't draw anything are left out
+    pub(super) fn groups(&self) -> SpriteGroups {
+        let mut groups = vec![];
+        for (sheet, instance) in &self.sprites {
+            if !instance.draws_nothing() {
+                push_grouped(&mut groups, sheet, *instance);
+            }
+        }
+        groups
+    }
+}
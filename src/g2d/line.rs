@@ -0,0 +1,319 @@
+// f32::clamp is newer than the Rust versions this crate supports
+#![allow(clippy::manual_clamp)]
+
+use super::*;
+
+/// How the ends of lines are drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineCap {
+    /// The line ends exactly at its end points
+    Butt,
+
+    /// The line extends past its end points by half its thickness
+    Square,
+
+    /// The line ends with a half circle around each end point
+    Round,
+}
+
+/// How the corners between segments of a polyline are drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineJoin {
+    /// The outer edges of the segments are extended until they meet.
+    /// Falls back to Bevel for very sharp corners
+    Miter,
+
+    /// The outer corners of the segments are connected with a straight edge
+    Bevel,
+
+    /// A circle is drawn at each corner
+    Round,
+}
+
+/// Miter joins longer than this (relative to the line thickness)
+/// are drawn as bevel joins instead
+const MITER_LIMIT: f32 = 4.0;
+
+/// Width and height in pixels of the sheet used to draw round caps and joins
+pub(super) const DISK_SHEET_SIZE: u32 = 64;
+
+/// RGBA pixels of a white disk filling a DISK_SHEET_SIZE x DISK_SHEET_SIZE
+/// image, with a soft edge so that it looks smooth when scaled
+pub(super) fn disk_pixels() -> Vec<u8> {
+    let size = DISK_SHEET_SIZE as f32;
+    let radius = size / 2.0;
+    let mut pixels = Vec::new();
+    for y in 0..DISK_SHEET_SIZE {
+        for x in 0..DISK_SHEET_SIZE {
+            let dx = x as f32 + 0.5 - radius;
+            let dy = y as f32 + 0.5 - radius;
+            let dist = (dx * dx + dy * dy).sqrt();
+            let alpha = (radius - dist + 0.5).max(0.0).min(1.0);
+            pixels.extend(&[255, 255, 255, (alpha * 255.0) as u8]);
+        }
+    }
+    pixels
+}
+
+/// A piece of a line to draw
+pub(super) enum LinePiece {
+    /// A rectangle drawn with the white sheet
+    Quad(Instance),
+
+    /// A circle with the given center and diameter, drawn with the disk sheet
+    Disk(Point, f32),
+}
+
+/// Breaks a polyline down into the pieces needed to draw it
+pub(super) fn polyline_pieces(
+    points: &[Point],
+    thickness: f32,
+    cap: LineCap,
+    join: LineJoin,
+) -> Vec<LinePiece> {
+    let half = thickness / 2.0;
+    let mut pieces = Vec::new();
+    if points.len() < 2 || thickness <= 0.0 {
+        return pieces;
+    }
+
+    // unit direction of each segment (None for zero length segments)
+    let dirs: Vec<Option<[f32; 2]>> = points
+        .windows(2)
+        .map(|pair| {
+            let [dx, dy] = [pair[1].x - pair[0].x, pair[1].y - pair[0].y];
+            let len = (dx * dx + dy * dy).sqrt();
+            if len > 0.0 {
+                Some([dx / len, dy / len])
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    // How far each segment should extend past each point
+    let cap_extension = if cap == LineCap::Square { half } else { 0.0 };
+    let mut extensions = vec![0.0; points.len()];
+    extensions[0] = cap_extension;
+    extensions[points.len() - 1] = cap_extension;
+
+    for i in 1..points.len() - 1 {
+        let (prev, next) = match (dirs[i - 1], dirs[i]) {
+            (Some(prev), Some(next)) => (prev, next),
+            _ => continue,
+        };
+        // turning angle between the two segments
+        let cos = (prev[0] * next[0] + prev[1] * next[1]).max(-1.0).min(1.0);
+        let theta = cos.acos();
+        if theta < 1e-4 {
+            // the segments are (almost) straight; no gap to fill
+            continue;
+        }
+        let miter_length = 1.0 / (theta / 2.0).cos();
+        match join {
+            LineJoin::Miter if miter_length <= MITER_LIMIT => {
+                extensions[i] = half * (theta / 2.0).tan();
+            }
+            LineJoin::Miter | LineJoin::Bevel => {
+                pieces.extend(bevel_piece(points[i], prev, next, half, theta));
+            }
+            LineJoin::Round => pieces.push(LinePiece::Disk(points[i], thickness)),
+        }
+    }
+
+    for i in 0..points.len() - 1 {
+        if let Some(dir) = dirs[i] {
+            let start = points[i] - [dir[0] * extensions[i], dir[1] * extensions[i]];
+            let end = points[i + 1] + [dir[0] * extensions[i + 1], dir[1] * extensions[i + 1]];
+            pieces.extend(segment_piece(start, end, dir, half));
+        }
+    }
+
+    if cap == LineCap::Round {
+        pieces.push(LinePiece::Disk(points[0], thickness));
+        pieces.push(LinePiece::Disk(points[points.len() - 1], thickness));
+    }
+    pieces
+}
+
+/// A rectangle `half * 2` thick from `start` to `end` (which are in direction `dir`)
+fn segment_piece(start: Point, end: Point, dir: [f32; 2], half: f32) -> Option<LinePiece> {
+    let center = (start + [end.x, end.y]) / 2.0;
+    let [dx, dy] = [end.x - start.x, end.y - start.y];
+    let half_len = (dx * dx + dy * dy).sqrt() / 2.0;
+    let dst = Rect::new(
+        center.x - half_len,
+        center.y - half,
+        center.x + half_len,
+        center.y + half,
+    )?;
+    let rotation = dir[1].atan2(dir[0]);
+    Some(LinePiece::Quad(Instance::from_rects(
+        dst,
+        [0.0, 0.0, 1.0, 1.0],
+        rotation,
+    )))
+}
+
+/// A rectangle covering the triangular gap on the outside of a corner,
+/// with its outer edge on the line between the outer corners of the segments
+fn bevel_piece(
+    joint: Point,
+    prev: [f32; 2],
+    next: [f32; 2],
+    half: f32,
+    theta: f32,
+) -> Option<LinePiece> {
+    // the outward bisector of the corner points away from the turn
+    let [tx, ty] = [prev[0] - next[0], prev[1] - next[1]];
+    let tlen = (tx * tx + ty * ty).sqrt();
+    let outward = [tx / tlen, ty / tlen];
+
+    // outer corners of each segment at the joint
+    let outer_normal = |dir: [f32; 2]| {
+        let normal = [-dir[1], dir[0]];
+        if normal[0] * outward[0] + normal[1] * outward[1] >= 0.0 {
+            normal
+        } else {
+            [dir[1], -dir[0]]
+        }
+    };
+    let [na, nb] = [outer_normal(prev), outer_normal(next)];
+    let ca = joint + [na[0] * half, na[1] * half];
+    let cb = joint + [nb[0] * half, nb[1] * half];
+
+    let [ex, ey] = [cb.x - ca.x, cb.y - ca.y];
+    let half_width = (ex * ex + ey * ey).sqrt() / 2.0;
+    let depth = half * (theta / 2.0).cos();
+    let edge_center = (ca + [cb.x, cb.y]) / 2.0;
+    let center = edge_center - [outward[0] * depth / 2.0, outward[1] * depth / 2.0];
+    let dst = Rect::new(
+        center.x - half_width,
+        center.y - depth / 2.0,
+        center.x + half_width,
+        center.y + depth / 2.0,
+    )?;
+    let rotation = ey.atan2(ex);
+    Some(LinePiece::Quad(Instance::from_rects(
+        dst,
+        [0.0, 0.0, 1.0, 1.0],
+        rotation,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    /// dst rect and rotation
+    type Quad = (Rect, f32);
+
+    /// center and diameter
+    type Disk = (Point, f32);
+
+    fn split(pieces: Vec<LinePiece>) -> (Vec<Quad>, Vec<Disk>) {
+        let mut quads = vec![];
+        let mut disks = vec![];
+        for piece in pieces {
+            match piece {
+                LinePiece::Quad(instance) => quads.push((instance.dest(), instance.rotation())),
+                LinePiece::Disk(center, diameter) => disks.push((center, diameter)),
+            }
+        }
+        (quads, disks)
+    }
+
+    fn assert_quad_near(actual: Quad, dst: [f32; 4], rotation: f32) {
+        let (rect, actual_rotation) = actual;
+        let [x1, y1] = rect.upper_left();
+        let [x2, y2] = rect.lower_right();
+        let near = |a: f32, b: f32| (a - b).abs() < 1e-4;
+        assert!(
+            near(x1, dst[0]) && near(y1, dst[1]) && near(x2, dst[2]) && near(y2, dst[3]),
+            "{:?} != {:?}",
+            rect,
+            dst,
+        );
+        assert!(
+            near(actual_rotation, rotation),
+            "{} != {}",
+            actual_rotation,
+            rotation
+        );
+    }
+
+    fn points(points: &[[f32; 2]]) -> Vec<Point> {
+        points.iter().map(|&p| p.into()).collect()
+    }
+
+    #[test]
+    fn straight_line_caps() {
+        let line = points(&[[0.0, 0.0], [10.0, 0.0]]);
+
+        let (quads, disks) = split(polyline_pieces(&line, 2.0, LineCap::Butt, LineJoin::Miter));
+        assert_eq!(quads.len(), 1);
+        assert_quad_near(quads[0], [0.0, -1.0, 10.0, 1.0], 0.0);
+        assert!(disks.is_empty());
+
+        let (quads, disks) = split(polyline_pieces(
+            &line,
+            2.0,
+            LineCap::Square,
+            LineJoin::Miter,
+        ));
+        assert_eq!(quads.len(), 1);
+        assert_quad_near(quads[0], [-1.0, -1.0, 11.0, 1.0], 0.0);
+        assert!(disks.is_empty());
+
+        let (quads, disks) = split(polyline_pieces(&line, 2.0, LineCap::Round, LineJoin::Miter));
+        assert_eq!(quads.len(), 1);
+        assert_quad_near(quads[0], [0.0, -1.0, 10.0, 1.0], 0.0);
+        assert_eq!(
+            disks,
+            vec![([0.0, 0.0].into(), 2.0), ([10.0, 0.0].into(), 2.0)]
+        );
+    }
+
+    #[test]
+    fn right_angle_miter() {
+        // Each segment is extended into the corner by half the thickness
+        let line = points(&[[0.0, 0.0], [10.0, 0.0], [10.0, 10.0]]);
+        let (quads, disks) = split(polyline_pieces(&line, 2.0, LineCap::Butt, LineJoin::Miter));
+        assert_eq!(quads.len(), 2);
+        assert_quad_near(quads[0], [0.0, -1.0, 11.0, 1.0], 0.0);
+        // (10, -1) to (10, 10), rotated a quarter turn about its center
+        assert_quad_near(quads[1], [4.5, 3.5, 15.5, 5.5], PI / 2.0);
+        assert!(disks.is_empty());
+    }
+
+    #[test]
+    fn sharp_miter_falls_back_to_bevel() {
+        // Turns back by almost 180 degrees, well past MITER_LIMIT
+        let line = points(&[[0.0, 0.0], [10.0, 0.0], [0.0, 2.0]]);
+        let (miter, _) = split(polyline_pieces(&line, 2.0, LineCap::Butt, LineJoin::Miter));
+        let (bevel, _) = split(polyline_pieces(&line, 2.0, LineCap::Butt, LineJoin::Bevel));
+
+        // the bevel piece, then both segments without any extension
+        assert_eq!(miter.len(), 3);
+        assert_quad_near(miter[1], [0.0, -1.0, 10.0, 1.0], 0.0);
+        for (m, b) in miter.iter().zip(&bevel) {
+            let [x1, y1] = b.0.upper_left();
+            let [x2, y2] = b.0.lower_right();
+            assert_quad_near(*m, [x1, y1, x2, y2], b.1);
+        }
+    }
+
+    #[test]
+    fn zero_length_segments_are_skipped() {
+        let line = points(&[[0.0, 0.0], [0.0, 0.0], [10.0, 0.0], [10.0, 0.0]]);
+        let (quads, disks) = split(polyline_pieces(&line, 2.0, LineCap::Butt, LineJoin::Round));
+        assert_eq!(quads.len(), 1);
+        assert_quad_near(quads[0], [0.0, -1.0, 10.0, 1.0], 0.0);
+        assert!(disks.is_empty());
+
+        let dot = points(&[[5.0, 5.0], [5.0, 5.0]]);
+        let (quads, _) = split(polyline_pieces(&dot, 2.0, LineCap::Butt, LineJoin::Miter));
+        assert!(quads.is_empty());
+    }
+}
@@ -1,53 +1,233 @@
 use crate::res;
 use crate::shaders;
+use crate::tween;
+use crate::Anchor;
 use crate::Color;
+use crate::Point;
 use crate::Rect;
 use crate::Result;
 use crate::Scaling;
+use crate::Transform2D;
 use crate::Translation;
 use raw_window_handle::HasRawWindowHandle;
-use std::rc::Rc;
+use std::collections::VecDeque;
+use std::ops::Range;
 use std::sync::Arc;
 use std::time::Duration;
+use std::time::Instant;
 
+mod adapter;
+mod align;
 mod batch;
+mod builder;
 mod iface;
 mod imp;
 mod inst;
+mod layer;
+mod line;
+mod multi;
+mod ninepatch;
+mod pipeline;
 mod sheet;
 mod sprite;
 
+use align::*;
 use batch::*;
-use inst::*;
-use sheet::*;
-use sprite::*;
+use pipeline::*;
 
+pub use adapter::*;
+pub use builder::*;
 pub use iface::*;
+pub use inst::*;
+pub use layer::*;
+pub use line::*;
+pub use multi::*;
+pub use ninepatch::*;
+pub use sheet::*;
+pub use sprite::*;
 
 pub const SLOT_LIMIT: usize = 16;
 
 pub const BATCH_SLOT_TEXT: usize = 0;
 pub const BATCH_SLOT_PIXEL: usize = 1;
 
+/// Solid background quads behind each cell of the text grid
+/// (drawn before the text itself, since slots are drawn in reverse order)
+pub const BATCH_SLOT_TEXT_BG: usize = 2;
+
 pub const DEFAULT_TEXT_NCOLS: usize = 80;
 
+/// Most steps that a single call to step_fixed runs, so that a long stall
+/// (e.g. the window being dragged) doesn't lead to a burst of catch-up steps
+pub const MAX_FIXED_STEPS_PER_CALL: usize = 8;
+
+/// Maximum number of rows of history kept by push_text_line
+pub const TEXT_SCROLLBACK_LIMIT: usize = 1000;
+
+/// Indices into the 4 corners of a sprite (see shader.vert)
+/// that make up the 2 triangles of the sprite
+const QUAD_INDICES: [u16; 6] = [0, 3, 2, 0, 2, 1];
+
+/// Send so that a Graphics2D can be moved to another thread
+/// (e.g. to render from an async server)
+type ErrorHandler = Box<dyn Fn(&str) + Send>;
+
+/// Data for the global uniform (Uniform in shader.vert)
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+struct ScaleUniform {
+    scale: Scaling,
+    framebuffer_size: [f32; 2],
+    /// 1.0 if vertices should be snapped to the nearest pixel, 0.0 otherwise
+    pixel_snapping: f32,
+    /// 1.0 if the y axis points up (see YAxis), 0.0 otherwise
+    y_up: f32,
+    /// Number of clockwise quarter turns to rotate the output by (see Orientation)
+    quarter_turns: f32,
+}
+
+unsafe impl bytemuck::Pod for ScaleUniform {}
+unsafe impl bytemuck::Zeroable for ScaleUniform {}
+
+struct ExtraSurface {
+    surface: wgpu::Surface,
+    sc_desc: wgpu::SwapChainDescriptor,
+    swap_chain: wgpu::SwapChain,
+}
+
+const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+struct DepthTexture {
+    /// Kept alive for as long as the view is in use
+    _texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    size: [u32; 2],
+}
+
 pub struct Graphics2D {
     surface: wgpu::Surface,
+    adapter_info: wgpu::AdapterInfo,
     device: Arc<wgpu::Device>,
     queue: wgpu::Queue,
     sc_desc: wgpu::SwapChainDescriptor,
     swap_chain: wgpu::SwapChain,
+
+    /// Windows other than the main one to draw to (see add_surface)
+    surfaces: Vec<ExtraSurface>,
+
     scale_uniform_bind_group_layout: wgpu::BindGroupLayout,
     translation_uniform_bind_group_layout: wgpu::BindGroupLayout,
-    render_pipeline: wgpu::RenderPipeline,
+    /// Kept so that the pipelines can be rebuilt with new shaders
+    /// (see reload_shaders) or in other variants (see set_debug_mode)
+    pipeline_layouts: PipelineLayouts,
+    shader_modules: ShaderModules,
+    pipelines: Pipelines,
+    depth_enabled: bool,
+
+    /// See set_depth_bias
+    depth_bias: i32,
+    depth_bias_slope_scale: f32,
+
+    /// While this isn't DebugMode::Normal, debug_pipelines (created with
+    /// debug_fs_module) are used in place of pipelines
+    debug_mode: DebugMode,
+    debug_fs_module: Option<wgpu::ShaderModule>,
+    debug_pipelines: Option<Pipelines>,
+    multi_texture_bind_group_layout: wgpu::BindGroupLayout,
+
+    /// If set, everything is drawn into this part of the target
+    /// (in the coordinates set by set_scale) instead of the whole target
+    viewport: Option<Rect>,
+
+    /// If false, each render draws over whatever is already in the target
+    clear_on_render: bool,
+
+    /// Only allocated while depth testing is enabled
+    depth_texture: Option<DepthTexture>,
+    index_buffer: wgpu::Buffer,
     texture_bind_group_layout: wgpu::BindGroupLayout,
+
+    /// Unique tag for texture_bind_group_layout, so that we can check
+    /// that a Sheet is compatible with render_pipeline before binding it
+    /// (e.g. sheets created with a different Graphics2D are not)
+    texture_layout_tag: usize,
     scale: Scaling,
+    scale_factor: f32,
+    y_axis: YAxis,
+    orientation: Orientation,
+    pixel_snapping: bool,
     scale_uniform_buffer: wgpu::Buffer,
 
     batches: [Option<Batch>; SLOT_LIMIT],
 
     text_grid_dim: Option<TextGridDim>,
 
+    /// Width of a single character cell when the text grid was initialized
+    /// (i.e. before any scaling applied with set_text_grid_char_width)
+    text_grid_base_char_width: f32,
+
+    /// Rows of text added with push_text_line (already wrapped to the grid's width),
+    /// oldest first, and how many rows up from the bottom are being viewed
+    text_scrollback: VecDeque<String>,
+    text_scroll: usize,
+
+    /// Offset and color of the text grid's drop shadow (see set_text_shadow)
+    text_shadow: Option<([f32; 2], Color)>,
+
+    /// Width and color of the text grid's outline (see set_text_outline)
+    text_outline: Option<(f32, Color)>,
+
+    /// Sprites queued with draw_sprite for the next render,
+    /// grouped by the sheet they are drawn from (in draw order)
+    immediate_sprites: SpriteGroups,
+
+    /// Named, retained groups of sprites, in the order they are drawn
+    layers: Vec<Layer>,
+
+    /// Retained groups of sprites from several sheets, in the order they are drawn
+    multi_sheet_batches: Vec<MultiSheetBatch>,
+
+    /// Reused between renders to collect the instances of a multi-sheet
+    /// batch that draw something, so that it isn't reallocated every frame
+    multi_sheet_scratch: Vec<Instance>,
+
+    /// 1x1 white sheet used to draw solid colored shapes
+    white_sheet: Option<Arc<Sheet>>,
+
+    /// Checkerboard returned by Sheet::placeholder
+    placeholder_sheet: Option<Arc<Sheet>>,
+
+    /// White disk used to draw round line caps and joins
+    disk_sheet: Option<Arc<Sheet>>,
+
+    line_cap: LineCap,
+    line_join: LineJoin,
+
+    last_frame_stats: RenderStats,
+
+    /// If set, force_render sleeps so that frames are at least
+    /// 1 / target_fps seconds apart (see set_target_fps)
+    target_fps: Option<f32>,
+    last_frame_time: Option<Instant>,
+
+    /// Time not yet covered by steps of step_fixed, and when it was last called
+    fixed_step_accumulator: Duration,
+    last_fixed_step_time: Option<Instant>,
+
+    /// Whether the window is currently minimized or otherwise not visible,
+    /// as reported by the application (see set_hidden)
+    hidden: bool,
+
+    /// If false, rendering is skipped while hidden (see set_render_when_hidden)
+    render_when_hidden: bool,
+
+    /// If set, rendering fails when any single batch or group of sprites
+    /// has more instances than this (see set_max_instances_per_batch)
+    max_instances_per_batch: Option<usize>,
+
+    /// Called with every error returned from flushing or rendering
+    error_handler: Option<ErrorHandler>,
+
     /// Used by render_if_dirty to determine if there's been
     /// any change since the last render
     dirty: bool,
@@ -0,0 +1,161 @@
+use super::*;
+
+/// The most sheets that a single MultiSheetBatch can draw from.
+/// This is the number of textures bound at once by the multi-sheet
+/// fragment shader (see `shaders::MULTI_FRAG`), so it can't be changed
+/// without also changing the shader
+pub const MAX_SHEETS_PER_BATCH: usize = 4;
+
+/// A named group of sprites that is kept across renders (like a Layer),
+/// but where each sprite can be drawn from any one of up to
+/// MAX_SHEETS_PER_BATCH sheets, while still taking a single draw call.
+///
+/// Useful when a scene interleaves sprites from a few shared sheets,
+/// which would otherwise have to be split into a group (and draw call)
+/// every time the sheet changes.
+///
+/// Multi-sheet batches are drawn in the order they were added with
+/// Graphics2D::add_multi_sheet_batch, beneath all layers, sprites
+/// queued with draw_sprite and batches.
+/// All sheets are sampled with Filter::Linear.
+pub struct MultiSheetBatch {
+    name: String,
+    visible: bool,
+    sheets: Vec<Arc<Sheet>>,
+    instances: Vec<Instance>,
+    bind_group: wgpu::BindGroup,
+}
+
+impl MultiSheetBatch {
+    pub(super) fn new(state: &Graphics2D, name: &str, sheets: &[Arc<Sheet>]) -> Result<Self> {
+        if sheets.is_empty() {
+            err!("A MultiSheetBatch needs at least one sheet");
+        }
+        if sheets.len() > MAX_SHEETS_PER_BATCH {
+            err!(
+                "Tried to create a MultiSheetBatch with {} sheets (max {})",
+                sheets.len(),
+                MAX_SHEETS_PER_BATCH,
+            );
+        }
+        for sheet in sheets {
+            if sheet.layout_tag() != state.texture_layout_tag {
+                err!(
+                    "Tried to add a Sheet to a MultiSheetBatch that is incompatible with \
+                    this Graphics2D (was it created with a different Graphics2D?)"
+                );
+            }
+        }
+        let device = &state.device;
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            lod_min_clamp: 0.0,
+            lod_max_clamp: 100.0,
+            compare: wgpu::CompareFunction::Always,
+        });
+
+        // Every texture binding has to be filled, so any unused ones
+        // just repeat the first sheet
+        let views: Vec<&wgpu::TextureView> = (0..MAX_SHEETS_PER_BATCH)
+            .map(|i| sheets.get(i).unwrap_or(&sheets[0]).view())
+            .collect();
+        let mut bindings: Vec<wgpu::Binding> = views
+            .iter()
+            .enumerate()
+            .map(|(i, view)| wgpu::Binding {
+                binding: i as u32,
+                resource: wgpu::BindingResource::TextureView(view),
+            })
+            .collect();
+        bindings.push(wgpu::Binding {
+            binding: MAX_SHEETS_PER_BATCH as u32,
+            resource: wgpu::BindingResource::Sampler(&sampler),
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &state.multi_texture_bind_group_layout,
+            bindings: &bindings,
+            label: Some("multi_sheet_bind_group"),
+        });
+        Ok(Self {
+            name: name.to_owned(),
+            visible: true,
+            sheets: sheets.to_vec(),
+            instances: vec![],
+            bind_group,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Hidden multi-sheet batches keep their sprites, but are skipped when rendering
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    /// The sheets that this batch draws from, in the order they were given
+    pub fn sheets(&self) -> &[Arc<Sheet>] {
+        &self.sheets
+    }
+
+    /// Adds a sprite described by `instance` to the end of this batch,
+    /// drawn from the sheet at `sheet_index` in `sheets()`.
+    /// Panics if sheet_index is out of bounds
+    pub fn push(&mut self, sheet_index: usize, mut instance: Instance) {
+        assert!(
+            sheet_index < self.sheets.len(),
+            "Sheet index {} out of bounds for a MultiSheetBatch with {} sheets",
+            sheet_index,
+            self.sheets.len(),
+        );
+        instance.set_sheet_index(sheet_index);
+        self.instances.push(instance);
+    }
+
+    pub fn instances(&self) -> &[Instance] {
+        &self.instances
+    }
+
+    /// The sprite at the given index in this batch, or None if it's out of bounds
+    pub fn get(&self, index: usize) -> Option<&Instance> {
+        self.instances.get(index)
+    }
+
+    /// Like `get`, but allows changing the sprite in place.
+    /// Its sheet can't be changed this way, since that is set by `push`
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Instance> {
+        self.instances.get_mut(index)
+    }
+
+    /// Removes all sprites from this batch (the sheets are kept)
+    pub fn clear(&mut self) {
+        self.instances.clear();
+    }
+
+    /// Frees memory held onto after this batch shrinks (see Layer::shrink_to_fit)
+    pub fn shrink_to_fit(&mut self) {
+        self.instances.shrink_to_fit();
+    }
+
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+
+    pub(super) fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+}
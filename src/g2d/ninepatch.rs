@@ -0,0 +1,164 @@
+use super::*;
+
+/// A region of a sheet that can be stretched to any size while keeping
+/// its corners intact (a.k.a. 9-slice scaling), e.g. for resizable UI panels.
+///
+/// The region is split into a 3x3 grid by the insets. When drawn,
+/// the corners keep their size, the edges stretch along one axis
+/// and the center stretches along both.
+pub struct NinePatch {
+    sheet: Arc<Sheet>,
+    src: Rect,
+    insets: [f32; 4],
+}
+
+impl NinePatch {
+    /// `src` is the region of the sheet to use (coordinates between 0 and 1)
+    /// and `insets` are the [left, top, right, bottom] widths of the borders
+    /// in pixels of the sheet.
+    /// When drawn, the borders are the same size in destination units
+    /// as they are in pixels of the sheet.
+    pub fn new<R: Into<Rect>>(sheet: Arc<Sheet>, src: R, insets: [f32; 4]) -> Self {
+        Self {
+            sheet,
+            src: src.into(),
+            insets,
+        }
+    }
+
+    pub fn sheet(&self) -> &Arc<Sheet> {
+        &self.sheet
+    }
+
+    /// Returns the instances needed to draw this nine patch to `dst`,
+    /// from left to right and top to bottom.
+    ///
+    /// If `dst` is smaller than the insets, the borders are shrunk
+    /// proportionally to fit, and pieces that would be empty are skipped
+    pub fn emit<R: Into<Rect>>(&self, dst: R) -> Vec<Instance> {
+        let [sheet_width, sheet_height] = self.sheet.dimensions();
+        let sheet_size = [sheet_width as f32, sheet_height as f32];
+        pieces(self.src, self.insets, sheet_size, dst.into())
+            .into_iter()
+            .map(|(src, dst)| Instance::builder().src(src).dest(dst).build())
+            .collect()
+    }
+}
+
+/// The [src, dst] rects of each non-empty piece of a nine patch,
+/// in the order described in `NinePatch::emit`
+fn pieces(src: Rect, insets: [f32; 4], sheet_size: [f32; 2], dst: Rect) -> Vec<(Rect, Rect)> {
+    let [left, top, right, bottom] = insets;
+    let [sheet_width, sheet_height] = sheet_size;
+    let [sx1, sy1] = src.upper_left();
+    let [sx2, sy2] = src.lower_right();
+    let src_xs = [
+        sx1,
+        sx1 + left / sheet_width,
+        sx2 - right / sheet_width,
+        sx2,
+    ];
+    let src_ys = [
+        sy1,
+        sy1 + top / sheet_height,
+        sy2 - bottom / sheet_height,
+        sy2,
+    ];
+
+    let [dx1, dy1] = dst.upper_left();
+    let [dx2, dy2] = dst.lower_right();
+    let shrink_x = ((dx2 - dx1) / (left + right)).min(1.0);
+    let shrink_y = ((dy2 - dy1) / (top + bottom)).min(1.0);
+    let dst_xs = [dx1, dx1 + left * shrink_x, dx2 - right * shrink_x, dx2];
+    let dst_ys = [dy1, dy1 + top * shrink_y, dy2 - bottom * shrink_y, dy2];
+
+    let mut pieces = Vec::new();
+    for row in 0..3 {
+        for col in 0..3 {
+            let src = Rect::new(src_xs[col], src_ys[row], src_xs[col + 1], src_ys[row + 1]);
+            let dst = Rect::new(dst_xs[col], dst_ys[row], dst_xs[col + 1], dst_ys[row + 1]);
+            if let (Some(src), Some(dst)) = (src, dst) {
+                pieces.push((src, dst));
+            }
+        }
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dsts(insets: [f32; 4], dst: [f32; 4]) -> Vec<Rect> {
+        let src = Rect::from([0.0, 0.0, 1.0, 1.0]);
+        pieces(src, insets, [32.0, 32.0], dst.into())
+            .into_iter()
+            .map(|(_, dst)| dst)
+            .collect::<Vec<_>>()
+    }
+
+    #[test]
+    fn emit_all_pieces() {
+        let expected: Vec<Rect> = vec![
+            // top left, top, top right
+            [0.0, 0.0, 4.0, 2.0].into(),
+            [4.0, 0.0, 94.0, 2.0].into(),
+            [94.0, 0.0, 100.0, 2.0].into(),
+            // left, center, right
+            [0.0, 2.0, 4.0, 42.0].into(),
+            [4.0, 2.0, 94.0, 42.0].into(),
+            [94.0, 2.0, 100.0, 42.0].into(),
+            // bottom left, bottom, bottom right
+            [0.0, 42.0, 4.0, 50.0].into(),
+            [4.0, 42.0, 94.0, 50.0].into(),
+            [94.0, 42.0, 100.0, 50.0].into(),
+        ];
+        assert_eq!(
+            dsts([4.0, 2.0, 6.0, 8.0], [0.0, 0.0, 100.0, 50.0]),
+            expected
+        );
+    }
+
+    #[test]
+    fn emit_src_follows_insets() {
+        let src = Rect::from([0.0, 0.0, 1.0, 1.0]);
+        let pieces = pieces(
+            src,
+            [8.0, 8.0, 8.0, 8.0],
+            [32.0, 32.0],
+            [0.0, 0.0, 64.0, 64.0].into(),
+        );
+        assert_eq!(pieces[0].0, Rect::from([0.0, 0.0, 0.25, 0.25]));
+        assert_eq!(pieces[4].0, Rect::from([0.25, 0.25, 0.75, 0.75]));
+        assert_eq!(pieces[8].0, Rect::from([0.75, 0.75, 1.0, 1.0]));
+    }
+
+    #[test]
+    fn emit_skips_zero_insets() {
+        // No left or right border: only the middle column is left
+        let expected: Vec<Rect> = vec![
+            [0.0, 0.0, 100.0, 2.0].into(),
+            [0.0, 2.0, 100.0, 42.0].into(),
+            [0.0, 42.0, 100.0, 50.0].into(),
+        ];
+        assert_eq!(
+            dsts([0.0, 2.0, 0.0, 8.0], [0.0, 0.0, 100.0, 50.0]),
+            expected
+        );
+
+        // No borders at all: just the center
+        let expected: Vec<Rect> = vec![[0.0, 0.0, 100.0, 50.0].into()];
+        assert_eq!(dsts([0.0; 4], [0.0, 0.0, 100.0, 50.0]), expected);
+    }
+
+    #[test]
+    fn emit_shrinks_borders() {
+        // dst is narrower than the left and right borders combined,
+        // so they're shrunk to fit and the middle column disappears
+        let expected: Vec<Rect> = vec![[0.0, 0.0, 5.0, 10.0].into(), [5.0, 0.0, 10.0, 10.0].into()];
+        assert_eq!(
+            dsts([10.0, 0.0, 10.0, 0.0], [0.0, 0.0, 10.0, 10.0]),
+            expected
+        );
+    }
+}
@@ -0,0 +1,232 @@
+use super::*;
+
+/// How the colors returned by the fragment shader are combined
+/// with what's already been drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Blend {
+    /// Regular alpha blending, used for everything except debugging
+    Alpha,
+
+    /// Colors are added together (see DebugMode::ShowOverdraw)
+    Additive,
+}
+
+/// Settings shared by every pipeline in a Pipelines
+#[derive(Debug, Clone, Copy)]
+pub(super) struct PipelineOptions {
+    /// The color format of the target (i.e. the swap chain)
+    pub(super) format: wgpu::TextureFormat,
+
+    pub(super) blend: Blend,
+
+    /// See Graphics2D::set_depth_bias
+    pub(super) depth_bias: i32,
+    pub(super) depth_bias_slope_scale: f32,
+}
+
+/// The shaders that the regular (non-debug) pipelines are created with
+pub(super) struct ShaderModules {
+    pub(super) vs: wgpu::ShaderModule,
+
+    /// For sprites drawn from a single sheet
+    pub(super) fs: wgpu::ShaderModule,
+
+    /// For MultiSheetBatches
+    pub(super) multi_fs: wgpu::ShaderModule,
+
+    /// For batches with a palette
+    pub(super) palette_fs: wgpu::ShaderModule,
+}
+
+/// The layouts that every pipeline is created with
+pub(super) struct PipelineLayouts {
+    /// For sprites drawn from a single sheet (with texture_bind_group_layout)
+    pub(super) sprite: wgpu::PipelineLayout,
+
+    /// For MultiSheetBatches (with multi_texture_bind_group_layout)
+    pub(super) multi: wgpu::PipelineLayout,
+
+    /// For batches with a palette (with texture_bind_group_layout for both
+    /// the sheet and the palette)
+    pub(super) palette: wgpu::PipelineLayout,
+}
+
+/// A pipeline for each kind of thing that gets drawn,
+/// with and without depth testing (see enable_depth)
+pub(super) struct Pipelines {
+    sprite: wgpu::RenderPipeline,
+    sprite_depth: wgpu::RenderPipeline,
+    multi: wgpu::RenderPipeline,
+    multi_depth: wgpu::RenderPipeline,
+    palette: wgpu::RenderPipeline,
+    palette_depth: wgpu::RenderPipeline,
+}
+
+impl Pipelines {
+    /// `fs_module` is used for sprites drawn from a single sheet,
+    /// `multi_fs_module` for MultiSheetBatches and `palette_fs_module`
+    /// for batches with a palette
+    pub(super) fn new(
+        device: &wgpu::Device,
+        layouts: &PipelineLayouts,
+        vs_module: &wgpu::ShaderModule,
+        fs_module: &wgpu::ShaderModule,
+        multi_fs_module: &wgpu::ShaderModule,
+        palette_fs_module: &wgpu::ShaderModule,
+        options: PipelineOptions,
+    ) -> Self {
+        let create = |layout, fs_module, depth| {
+            create_render_pipeline(device, layout, vs_module, fs_module, options, depth)
+        };
+        Self {
+            sprite: create(&layouts.sprite, fs_module, false),
+            sprite_depth: create(&layouts.sprite, fs_module, true),
+            multi: create(&layouts.multi, multi_fs_module, false),
+            multi_depth: create(&layouts.multi, multi_fs_module, true),
+            palette: create(&layouts.palette, palette_fs_module, false),
+            palette_depth: create(&layouts.palette, palette_fs_module, true),
+        }
+    }
+
+    pub(super) fn sprite(&self, depth: bool) -> &wgpu::RenderPipeline {
+        if depth {
+            &self.sprite_depth
+        } else {
+            &self.sprite
+        }
+    }
+
+    pub(super) fn multi(&self, depth: bool) -> &wgpu::RenderPipeline {
+        if depth {
+            &self.multi_depth
+        } else {
+            &self.multi
+        }
+    }
+
+    pub(super) fn palette(&self, depth: bool) -> &wgpu::RenderPipeline {
+        if depth {
+            &self.palette_depth
+        } else {
+            &self.palette
+        }
+    }
+}
+
+/// Creates a shader module from compiled SPIR-V.
+/// `kind` describes the shader in the error if the SPIR-V is invalid
+pub(super) fn create_shader_module(
+    device: &wgpu::Device,
+    spirv: &[u8],
+    kind: &str,
+) -> Result<wgpu::ShaderModule> {
+    match wgpu::read_spirv(std::io::Cursor::new(spirv)) {
+        Ok(data) => Ok(device.create_shader_module(&data)),
+        Err(error) => err!("Invalid {} SPIR-V: {}", kind, error),
+    }
+}
+
+/// The wgpu description of Instance::ATTRIBUTES.
+/// Panics if an attribute has more than 4 components, or doesn't fit in an Instance
+fn vertex_attributes() -> Vec<wgpu::VertexAttributeDescriptor> {
+    Instance::ATTRIBUTES
+        .iter()
+        .map(|attribute| {
+            assert!(
+                attribute.offset + attribute.components * std::mem::size_of::<f32>()
+                    <= Instance::STRIDE,
+                "Instance attribute at location {} doesn't fit in the instance",
+                attribute.shader_location,
+            );
+            wgpu::VertexAttributeDescriptor {
+                offset: attribute.offset as wgpu::BufferAddress,
+                shader_location: attribute.shader_location,
+                format: match attribute.components {
+                    1 => wgpu::VertexFormat::Float,
+                    2 => wgpu::VertexFormat::Float2,
+                    3 => wgpu::VertexFormat::Float3,
+                    4 => wgpu::VertexFormat::Float4,
+                    n => panic!(
+                        "Instance attribute at location {} has {} components (max 4)",
+                        attribute.shader_location, n,
+                    ),
+                },
+            }
+        })
+        .collect()
+}
+
+/// Creates a pipeline to draw sprites with, with or without depth testing
+fn create_render_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    vs_module: &wgpu::ShaderModule,
+    fs_module: &wgpu::ShaderModule,
+    options: PipelineOptions,
+    depth: bool,
+) -> wgpu::RenderPipeline {
+    let attributes = vertex_attributes();
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        layout,
+        vertex_stage: wgpu::ProgrammableStageDescriptor {
+            module: vs_module,
+            entry_point: "main",
+        },
+        fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
+            module: fs_module,
+            entry_point: "main",
+        }),
+        rasterization_state: Some(wgpu::RasterizationStateDescriptor {
+            front_face: wgpu::FrontFace::Ccw,
+            // Sprites are always screen-facing quads, so culling buys us nothing.
+            // Worse, anything that flips a sprite (e.g. a mirrored dst or src rect)
+            // inverts its winding, and back-face culling would make it vanish.
+            cull_mode: wgpu::CullMode::None,
+            depth_bias: options.depth_bias,
+            depth_bias_slope_scale: options.depth_bias_slope_scale,
+            depth_bias_clamp: 0.0,
+        }),
+        color_states: &[wgpu::ColorStateDescriptor {
+            format: options.format,
+            color_blend: match options.blend {
+                Blend::Alpha => wgpu::BlendDescriptor {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                Blend::Additive => wgpu::BlendDescriptor {
+                    src_factor: wgpu::BlendFactor::One,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            },
+            alpha_blend: wgpu::BlendDescriptor::REPLACE,
+            write_mask: wgpu::ColorWrite::ALL,
+        }],
+        primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+        depth_stencil_state: if depth {
+            Some(wgpu::DepthStencilStateDescriptor {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil_front: wgpu::StencilStateFaceDescriptor::IGNORE,
+                stencil_back: wgpu::StencilStateFaceDescriptor::IGNORE,
+                stencil_read_mask: 0,
+                stencil_write_mask: 0,
+            })
+        } else {
+            None
+        },
+        vertex_state: wgpu::VertexStateDescriptor {
+            index_format: wgpu::IndexFormat::Uint16,
+            vertex_buffers: &[wgpu::VertexBufferDescriptor {
+                stride: Instance::STRIDE as wgpu::BufferAddress,
+                step_mode: wgpu::InputStepMode::Instance,
+                attributes: &attributes,
+            }],
+        },
+        sample_count: 1,
+        sample_mask: !0,
+        alpha_to_coverage_enabled: false,
+    })
+}
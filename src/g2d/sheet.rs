@@ -1,8 +1,86 @@
 use super::*;
+use std::collections::HashMap;
+use std::path::Path;
 
-/// An image loaded in GPU memory ready to be used with a Batch
-pub(super) struct Sheet {
+/// The largest width or height of a texture that a2d will try to create.
+/// This is the minimum that all wgpu backends are expected to support.
+pub const MAX_TEXTURE_DIMENSION: u32 = 8192;
+
+/// Number of bytes per pixel in the raw RGBA8 data that sheets are created from
+/// (see `Sheet::from_raw_rgba`)
+pub const BYTES_PER_PIXEL: usize = 4;
+
+/// Additional ways a Sheet may be used, beyond being drawn from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SheetUsage {
+    /// Allow copying data out of the sheet (e.g. to read it back)
+    pub copy_src: bool,
+
+    /// Allow rendering into the sheet
+    pub render_target: bool,
+
+    /// Wrap around instead of stretching the edges when sprites sample
+    /// outside of the sheet (i.e. src coordinates outside 0 to 1),
+    /// so that the sheet can be tiled (see Instance::set_tiled_src)
+    pub repeat: bool,
+}
+
+impl SheetUsage {
+    fn to_wgpu(self) -> wgpu::TextureUsage {
+        let mut usage = wgpu::TextureUsage::empty();
+        if self.copy_src {
+            usage |= wgpu::TextureUsage::COPY_SRC;
+        }
+        if self.render_target {
+            usage |= wgpu::TextureUsage::OUTPUT_ATTACHMENT;
+        }
+        usage
+    }
+}
+
+/// How a sheet is sampled when a sprite is drawn larger or smaller than
+/// its size in the sheet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    /// Blends neighboring pixels, for smooth results (the default)
+    Linear,
+
+    /// Uses the closest pixel, keeping pixel art crisp
+    Nearest,
+}
+
+// Not derived, since #[default] on enum variants is newer than the
+// Rust versions this crate supports
+#[allow(clippy::derivable_impls)]
+impl Default for Filter {
+    fn default() -> Self {
+        Filter::Linear
+    }
+}
+
+/// A shared handle to a Sheet, as returned by all of Sheet's constructors.
+///
+/// This is an Arc (rather than an Rc) so that sheets can be passed to
+/// and shared with other threads (e.g. ones managing assets)
+pub type SheetHandle = Arc<Sheet>;
+
+/// An image loaded in GPU memory ready to be used to draw sprites
+pub struct Sheet {
+    texture: wgpu::Texture,
+
+    /// Kept so that the sheet can also be bound as part of a MultiSheetBatch
+    view: wgpu::TextureView,
+    width: u32,
+    height: u32,
+    usage: SheetUsage,
     bind_group: wgpu::BindGroup,
+
+    /// Same as bind_group, but samples with Filter::Nearest
+    nearest_bind_group: wgpu::BindGroup,
+
+    /// Identifies the bind group layout `bind_group` was created with
+    /// (see Graphics2D::texture_layout_tag)
+    layout_tag: usize,
 }
 
 impl Sheet {
@@ -10,13 +88,224 @@ impl Sheet {
     ///
     /// The bytes are interpreted by passing the bytes to the
     /// `load_from_memory` function from the `image` crate
-    pub fn from_bytes(state: &mut Graphics2D, diffuse_bytes: &[u8]) -> Result<Rc<Self>> {
-        let diffuse_image = image::load_from_memory(diffuse_bytes)?;
-        let diffuse_rgba = diffuse_image.to_rgba();
+    pub fn from_bytes(state: &mut Graphics2D, diffuse_bytes: &[u8]) -> Result<Arc<Self>> {
+        let diffuse_rgba = decode_rgba(diffuse_bytes)?;
         Self::from_rbga_image(state, diffuse_rgba)
     }
 
-    pub fn from_color<C: Into<Color>>(state: &mut Graphics2D, color: C) -> Result<Rc<Self>> {
+    /// Like `from_bytes`, but returns the placeholder sheet (see `placeholder`)
+    /// instead of an error if the bytes can't be decoded, so that a missing or
+    /// broken image shows up as a checkerboard instead of stopping the program.
+    ///
+    /// Errors from the GPU are still returned.
+    pub fn from_bytes_or_placeholder(
+        state: &mut Graphics2D,
+        diffuse_bytes: &[u8],
+    ) -> Result<Arc<Self>> {
+        match decode_rgba(diffuse_bytes) {
+            Ok(diffuse_rgba) => Self::from_rbga_image(state, diffuse_rgba),
+            Err(_) => Self::placeholder(state),
+        }
+    }
+
+    /// A magenta and black checkerboard, meant to stand in for images that
+    /// failed to load. The sheet is only created once per Graphics2D,
+    /// and shared by every call
+    pub fn placeholder(state: &mut Graphics2D) -> Result<Arc<Self>> {
+        state.placeholder_sheet()
+    }
+
+    /// Like `from_bytes`, but allows the sheet to be used in
+    /// additional ways (see `SheetUsage`)
+    pub fn from_bytes_with_usage(
+        state: &mut Graphics2D,
+        diffuse_bytes: &[u8],
+        usage: SheetUsage,
+    ) -> Result<Arc<Self>> {
+        let diffuse_rgba = decode_rgba(diffuse_bytes)?;
+        Self::from_rbga_image_with_usage(state, diffuse_rgba, usage)
+    }
+
+    /// Like `from_bytes`, but treats pixels matching the color `key`
+    /// (e.g. a magenta background) as fully transparent.
+    /// This is useful for older sprite sheets that don't have an alpha channel.
+    ///
+    /// Matching pixels are made transparent black rather than keeping the
+    /// key's color, so that it doesn't bleed into the edges of sprites
+    /// when they're drawn with Filter::Linear.
+    pub fn from_bytes_color_key(
+        state: &mut Graphics2D,
+        diffuse_bytes: &[u8],
+        key: [u8; 3],
+    ) -> Result<Arc<Self>> {
+        /// How far each channel may be from the key and still match
+        const TOLERANCE: u8 = 2;
+        let mut diffuse_rgba = decode_rgba(diffuse_bytes)?;
+        for pixel in diffuse_rgba.pixels_mut() {
+            if (0..3).all(|i| (pixel[i] as i16 - key[i] as i16).abs() <= TOLERANCE as i16) {
+                *pixel = image::Rgba([0, 0, 0, 0]);
+            }
+        }
+        Self::from_rbga_image(state, diffuse_rgba)
+    }
+
+    /// Loads every image in `paths` and packs them together into a
+    /// single sheet (an atlas), so that they can all be drawn without
+    /// switching sheets.
+    ///
+    /// Returns the sheet along with the src rect of each image in the sheet,
+    /// keyed by the name paired with its path.
+    ///
+    /// Images are packed into rows ("shelves") from tallest to shortest,
+    /// right next to each other, so with Filter::Linear the edges of a sprite
+    /// may pick up a faint seam from its neighbors. Passing its region through
+    /// `inset_half_texel` prevents this.
+    /// Returns an error if the images don't fit in a
+    /// MAX_TEXTURE_DIMENSION x MAX_TEXTURE_DIMENSION texture.
+    pub fn pack_from_paths<P: AsRef<Path>>(
+        state: &mut Graphics2D,
+        paths: &[(&str, P)],
+    ) -> Result<(Arc<Self>, HashMap<String, Rect>)> {
+        let mut images = Vec::new();
+        for (name, path) in paths {
+            let image = decode_rgba(&std::fs::read(path)?)?;
+            let (width, height) = image.dimensions();
+            if width == 0 || height == 0 {
+                err!("Image {:?} for Sheet atlas is empty", name);
+            }
+            if width > MAX_TEXTURE_DIMENSION || height > MAX_TEXTURE_DIMENSION {
+                err!(
+                    "Image {:?} ({}x{}) is too large for a Sheet atlas (max {})",
+                    name,
+                    width,
+                    height,
+                    MAX_TEXTURE_DIMENSION,
+                );
+            }
+            images.push((*name, image));
+        }
+        if images.is_empty() {
+            err!("No images to pack into Sheet atlas");
+        }
+        images.sort_by_key(|(_, image)| std::cmp::Reverse(image.height()));
+
+        // Aim for a roughly square atlas, but always wide enough for the widest image
+        let area: u64 = images
+            .iter()
+            .map(|(_, image)| image.width() as u64 * image.height() as u64)
+            .sum();
+        let max_width = images.iter().map(|(_, image)| image.width()).max().unwrap();
+        let atlas_width = ((area as f64).sqrt().ceil() as u32)
+            .next_power_of_two()
+            .max(max_width)
+            .min(MAX_TEXTURE_DIMENSION);
+
+        // shelf packing
+        let mut positions = Vec::new();
+        let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+        for (_, image) in &images {
+            if x + image.width() > atlas_width {
+                x = 0;
+                y += shelf_height;
+                shelf_height = 0;
+            }
+            positions.push((x, y));
+            x += image.width();
+            shelf_height = shelf_height.max(image.height());
+        }
+        let atlas_height = y + shelf_height;
+        if atlas_height > MAX_TEXTURE_DIMENSION {
+            err!(
+                "Images don't fit in a {}x{} Sheet atlas",
+                MAX_TEXTURE_DIMENSION,
+                MAX_TEXTURE_DIMENSION,
+            );
+        }
+
+        let mut atlas = image::RgbaImage::new(atlas_width, atlas_height);
+        let mut regions = HashMap::new();
+        for ((name, image), (x, y)) in images.iter().zip(positions) {
+            image::imageops::replace(&mut atlas, image, x, y);
+            let [aw, ah] = [atlas_width as f32, atlas_height as f32];
+            let region = [
+                x as f32 / aw,
+                y as f32 / ah,
+                (x + image.width()) as f32 / aw,
+                (y + image.height()) as f32 / ah,
+            ];
+            regions.insert(name.to_string(), region.into());
+        }
+        Ok((Self::from_rbga_image(state, atlas)?, regions))
+    }
+
+    /// Decodes every frame of an animated GIF and packs them into a grid
+    /// in a single sheet.
+    ///
+    /// Returns the sheet along with the src rect of each frame (in order)
+    /// and how long each frame should be shown for.
+    ///
+    /// Every frame is decoded into memory at the full size of the GIF before
+    /// being uploaded, so a long GIF takes roughly
+    /// `4 * width * height * number_of_frames` bytes of both CPU and GPU memory.
+    /// Returns an error if the frames don't fit in a
+    /// MAX_TEXTURE_DIMENSION x MAX_TEXTURE_DIMENSION texture.
+    pub fn from_gif_bytes(
+        state: &mut Graphics2D,
+        bytes: &[u8],
+    ) -> Result<(Arc<Self>, Vec<Rect>, Vec<Duration>)> {
+        use image::AnimationDecoder;
+        let decoder = image::codecs::gif::GifDecoder::new(std::io::Cursor::new(bytes))?;
+        let frames = decoder.into_frames().collect_frames()?;
+        if frames.is_empty() {
+            err!("GIF has no frames");
+        }
+        let (frame_width, frame_height) = frames[0].buffer().dimensions();
+        if frame_width == 0 || frame_height == 0 {
+            err!("GIF frames are empty");
+        }
+
+        // Frames all have the same size, so lay them out in a roughly square grid
+        let nframes = frames.len() as u32;
+        let ncols = (nframes as f64).sqrt().ceil() as u32;
+        // (not u32::div_ceil, which is newer than the Rust versions this crate supports)
+        #[allow(clippy::manual_div_ceil)]
+        let nrows = (nframes + ncols - 1) / ncols;
+        let (atlas_width, atlas_height) = (ncols * frame_width, nrows * frame_height);
+        if atlas_width > MAX_TEXTURE_DIMENSION || atlas_height > MAX_TEXTURE_DIMENSION {
+            err!(
+                "{} frames of {}x{} are too large for a single Sheet (max {}x{})",
+                nframes,
+                frame_width,
+                frame_height,
+                MAX_TEXTURE_DIMENSION,
+                MAX_TEXTURE_DIMENSION,
+            );
+        }
+
+        let mut atlas = image::RgbaImage::new(atlas_width, atlas_height);
+        let mut rects = Vec::new();
+        let mut durations = Vec::new();
+        for (i, frame) in frames.into_iter().enumerate() {
+            let (numer, denom) = frame.delay().numer_denom_ms();
+            durations.push(Duration::from_secs_f64(
+                numer as f64 / denom as f64 / 1000.0,
+            ));
+            let x = (i as u32 % ncols) * frame_width;
+            let y = (i as u32 / ncols) * frame_height;
+            image::imageops::replace(&mut atlas, &frame.into_buffer(), x, y);
+            let [aw, ah] = [atlas_width as f32, atlas_height as f32];
+            let rect = [
+                x as f32 / aw,
+                y as f32 / ah,
+                (x + frame_width) as f32 / aw,
+                (y + frame_height) as f32 / ah,
+            ];
+            rects.push(rect.into());
+        }
+        Ok((Self::from_rbga_image(state, atlas)?, rects, durations))
+    }
+
+    pub fn from_color<C: Into<Color>>(state: &mut Graphics2D, color: C) -> Result<Arc<Self>> {
         Self::from_colors::<C, Vec<C>>(state, 1, 1, vec![color])
     }
 
@@ -25,7 +314,7 @@ impl Sheet {
         width: u32,
         height: u32,
         colors: V,
-    ) -> Result<Rc<Self>>
+    ) -> Result<Arc<Self>>
     where
         C: Into<Color>,
         V: IntoIterator<Item = C>,
@@ -39,12 +328,38 @@ impl Sheet {
         Self::from_rgba_bytes(state, width, height, pixels)
     }
 
+    /// Creates a sheet from raw, tightly packed RGBA8 pixel data
+    /// (i.e. `BYTES_PER_PIXEL * width` bytes per row, rows from top to bottom,
+    /// with the color values in sRGB).
+    ///
+    /// This is the lowest-level way to create a sheet, for loaders that
+    /// decode images themselves. Returns an error if `data` is not exactly
+    /// `BYTES_PER_PIXEL * width * height` bytes long.
+    pub fn from_raw_rgba(
+        state: &mut Graphics2D,
+        width: u32,
+        height: u32,
+        data: &[u8],
+    ) -> Result<Arc<Self>> {
+        let expected = BYTES_PER_PIXEL * width as usize * height as usize;
+        if data.len() != expected {
+            err!(
+                "Expected {} bytes of RGBA data for a {}x{} Sheet, but got {}",
+                expected,
+                width,
+                height,
+                data.len(),
+            );
+        }
+        Self::from_rgba_bytes(state, width, height, data.to_vec())
+    }
+
     pub fn from_rgba_bytes(
         state: &mut Graphics2D,
         width: u32,
         height: u32,
         bytes: Vec<u8>,
-    ) -> Result<Rc<Self>> {
+    ) -> Result<Arc<Self>> {
         let rgba = match image::RgbaImage::from_raw(width, height, bytes) {
             Some(img) => img,
             None => err!("Failed to create image from rgba bytes for Sheet"),
@@ -56,18 +371,47 @@ impl Sheet {
     /// as a dependency.
     /// The version of `image` we use might not match with the version
     /// that the binary crate uses.
-    fn from_rbga_image(state: &mut Graphics2D, diffuse_rgba: image::RgbaImage) -> Result<Rc<Self>> {
+    fn from_rbga_image(
+        state: &mut Graphics2D,
+        diffuse_rgba: image::RgbaImage,
+    ) -> Result<Arc<Self>> {
+        Self::from_rbga_image_with_usage(state, diffuse_rgba, SheetUsage::default())
+    }
+
+    fn from_rbga_image_with_usage(
+        state: &mut Graphics2D,
+        diffuse_rgba: image::RgbaImage,
+        usage: SheetUsage,
+    ) -> Result<Arc<Self>> {
         let device = &state.device;
         let texture_bind_group_layout = &state.texture_bind_group_layout;
         let queue = &state.queue;
 
         let dimensions = diffuse_rgba.dimensions();
+        if dimensions.0 == 0 || dimensions.1 == 0 {
+            err!(
+                "Tried to create an empty ({}x{}) Sheet",
+                dimensions.0,
+                dimensions.1
+            );
+        }
+        let max = state.max_texture_dimension();
+        if dimensions.0 > max || dimensions.1 > max {
+            err!(
+                "Image ({}x{}) is too large for a Sheet (max {}x{})",
+                dimensions.0,
+                dimensions.1,
+                max,
+                max,
+            );
+        }
         let size = wgpu::Extent3d {
             width: dimensions.0,
             height: dimensions.1,
             depth: 1,
         };
-        let buffer = device.create_buffer_with_data(&diffuse_rgba, wgpu::BufferUsage::COPY_SRC);
+        let padded = pad_rows(&diffuse_rgba, dimensions.0, dimensions.1);
+        let buffer = imp::create_buffer_checked(device, &padded, wgpu::BufferUsage::COPY_SRC);
         let diffuse_texture = device.create_texture(&wgpu::TextureDescriptor {
             // All textures are stored as 3d, we represent our 2d texture
             // by setting depth to 1.
@@ -85,7 +429,7 @@ impl Sheet {
             format: wgpu::TextureFormat::Rgba8UnormSrgb,
             // SAMPLED tells wgpu that we want to use this texture in shaders
             // COPY_DST means that we want to copy data to this texture
-            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST | usage.to_wgpu(),
             label: Some("diffuse_texture"),
         });
         {
@@ -97,7 +441,7 @@ impl Sheet {
                 wgpu::BufferCopyView {
                     buffer: &buffer,
                     offset: 0,
-                    bytes_per_row: 4 * dimensions.0,
+                    bytes_per_row: padded_bytes_per_row(dimensions.0),
                     rows_per_image: dimensions.1,
                 },
                 wgpu::TextureCopyView {
@@ -113,36 +457,129 @@ impl Sheet {
         }
         let diffuse_texture_view = diffuse_texture.create_default_view();
 
-        let diffuse_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Linear,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            lod_min_clamp: -100.0,
-            lod_max_clamp: 100.0,
-            compare: wgpu::CompareFunction::Always,
-        });
+        // wgpu 0.5 only has ClampToEdge, Repeat and MirrorRepeat (there is no
+        // ClampToBorder or border color yet). For sprites that should fade to
+        // transparent outside of their image, give the image a 1 pixel
+        // transparent border instead, which ClampToEdge then extends.
+        let address_mode = if usage.repeat {
+            wgpu::AddressMode::Repeat
+        } else {
+            wgpu::AddressMode::ClampToEdge
+        };
+        let create_bind_group = |filter: wgpu::FilterMode, label: &str| {
+            let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                address_mode_u: address_mode,
+                address_mode_v: address_mode,
+                address_mode_w: address_mode,
+                mag_filter: filter,
+                min_filter: filter,
+                // Sheets only ever have a single mip level, so mipmap_filter
+                // and the LOD clamps have no visible effect yet. If mipmaps are
+                // added, the clamps limit which levels mipmap_filter picks from,
+                // and 0.0 (the full-size level) is the meaningful minimum.
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                lod_min_clamp: 0.0,
+                lod_max_clamp: 100.0,
+                compare: wgpu::CompareFunction::Always,
+            });
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &texture_bind_group_layout,
+                bindings: &[
+                    wgpu::Binding {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&diffuse_texture_view),
+                    },
+                    wgpu::Binding {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&sampler),
+                    },
+                ],
+                label: Some(label),
+            })
+        };
 
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &texture_bind_group_layout,
-            bindings: &[
-                wgpu::Binding {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&diffuse_texture_view),
-                },
-                wgpu::Binding {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&diffuse_sampler),
-                },
-            ],
-            label: Some("diffuse_bind_group"),
-        });
-        Ok(Rc::new(Self { bind_group }))
+        // The same texture with each kind of filtering, so that batches can
+        // pick a filter without uploading the sheet twice
+        let bind_group = create_bind_group(wgpu::FilterMode::Linear, "diffuse_bind_group");
+        let nearest_bind_group =
+            create_bind_group(wgpu::FilterMode::Nearest, "nearest_diffuse_bind_group");
+        Ok(Arc::new(Self {
+            texture: diffuse_texture,
+            view: diffuse_texture_view,
+            width: dimensions.0,
+            height: dimensions.1,
+            usage,
+            bind_group,
+            nearest_bind_group,
+            layout_tag: state.texture_layout_tag,
+        }))
+    }
+
+    /// The [width, height] of the sheet in pixels
+    pub fn dimensions(&self) -> [u32; 2] {
+        [self.width, self.height]
+    }
+
+    pub fn usage(&self) -> SheetUsage {
+        self.usage
+    }
+
+    /// The [width, height] of a single pixel of the sheet,
+    /// in the coordinates of src rects (i.e. fractions of the sheet)
+    pub fn texel_size(&self) -> [f32; 2] {
+        [1.0 / self.width as f32, 1.0 / self.height as f32]
+    }
+
+    /// Shrinks a src rect in this sheet by half a pixel on every side, so that
+    /// linear filtering never blends in pixels from just outside of it
+    /// (e.g. from neighboring images in an atlas, see `pack_from_paths`).
+    ///
+    /// Each side moves in by at most a quarter of the rect's size, so that
+    /// rects only a pixel or so across don't collapse
+    pub fn inset_half_texel(&self, rect: Rect) -> Rect {
+        let [x1, y1] = rect.upper_left();
+        let [x2, y2] = rect.lower_right();
+        let [tw, th] = self.texel_size();
+        let dx = (tw / 2.0).min((x2 - x1) / 4.0);
+        let dy = (th / 2.0).min((y2 - y1) / 4.0);
+        [x1 + dx, y1 + dy, x2 - dx, y2 - dy].into()
     }
 
-    pub fn bind_group(&self) -> &wgpu::BindGroup {
+    #[allow(dead_code)]
+    pub(super) fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
+    pub(super) fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    pub(super) fn bind_group(&self) -> &wgpu::BindGroup {
         &self.bind_group
     }
+
+    pub(super) fn bind_group_with_filter(&self, filter: Filter) -> &wgpu::BindGroup {
+        match filter {
+            Filter::Linear => &self.bind_group,
+            Filter::Nearest => &self.nearest_bind_group,
+        }
+    }
+
+    pub(super) fn layout_tag(&self) -> usize {
+        self.layout_tag
+    }
+}
+
+/// Decodes image bytes by passing them to the `load_from_memory`
+/// function from the `image` crate
+fn decode_rgba(bytes: &[u8]) -> Result<image::RgbaImage> {
+    let image = image::load_from_memory(bytes)?;
+    Ok(image.to_rgba())
+}
+
+/// Sheets are shared between threads through SheetHandle
+#[allow(dead_code)]
+fn assert_sheet_is_send_and_sync() {
+    fn check<T: Send + Sync>() {}
+    check::<Sheet>();
 }
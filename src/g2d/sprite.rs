@@ -13,3 +13,51 @@ pub(super) struct SpriteDesc {
     /// The color factor to apply to this sprite
     pub color: Color,
 }
+
+/// A sheet together with how to draw it, for simple programs that
+/// don't want to manage layers or batches (see Graphics2D::draw).
+///
+/// Setters take and return self, so that sprites can be built up
+/// in a single expression
+#[derive(Clone)]
+pub struct Sprite {
+    sheet: Arc<Sheet>,
+    instance: Instance,
+}
+
+impl Sprite {
+    /// A sprite that draws the `src` part of `sheet` (coordinates between 0 and 1)
+    /// to `dst` (in the coordinates set by `set_scale`)
+    pub fn new<R1: Into<Rect>, R2: Into<Rect>>(sheet: &Arc<Sheet>, dst: R1, src: R2) -> Self {
+        Self {
+            sheet: sheet.clone(),
+            instance: Instance::from_rects(dst, src, 0.0),
+        }
+    }
+
+    /// Clockwise rotation in radians around the center of `dst`
+    pub fn rotation(mut self, rotation: f32) -> Self {
+        self.instance.set_rotation(rotation);
+        self
+    }
+
+    /// The color factor to multiply the sheet's colors with
+    pub fn color<C: Into<Color>>(mut self, color: C) -> Self {
+        self.instance.set_color_factor(color);
+        self
+    }
+
+    pub fn sheet(&self) -> &Arc<Sheet> {
+        &self.sheet
+    }
+
+    pub fn instance(&self) -> &Instance {
+        &self.instance
+    }
+
+    /// For changing anything not covered by Sprite's own setters
+    /// (e.g. moving the sprite with `set_dest`)
+    pub fn instance_mut(&mut self) -> &mut Instance {
+        &mut self.instance
+    }
+}
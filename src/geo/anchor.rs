@@ -0,0 +1,33 @@
+/// A point on a rectangle that a position can refer to
+/// (e.g. to place a sprite by its center rather than its upper-left corner)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    /// Where this anchor is on a rectangle, as a fraction of its
+    /// [width, height] from the upper-left corner
+    pub fn to_fraction(self) -> [f32; 2] {
+        match self {
+            Anchor::TopLeft => [0.0, 0.0],
+            Anchor::Top => [0.5, 0.0],
+            Anchor::TopRight => [1.0, 0.0],
+            Anchor::Left => [0.0, 0.5],
+            Anchor::Center => [0.5, 0.5],
+            Anchor::Right => [1.0, 0.5],
+            Anchor::BottomLeft => [0.0, 1.0],
+            Anchor::Bottom => [0.5, 1.0],
+            Anchor::BottomRight => [1.0, 1.0],
+        }
+    }
+}
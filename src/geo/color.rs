@@ -1,4 +1,7 @@
+use crate::tween::lerp;
+
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Color([f32; 4]);
 
 impl Color {
@@ -6,6 +9,18 @@ impl Color {
         self.0
     }
 
+    /// Interpolates each channel between `self` (when `t` is 0)
+    /// and `other` (when `t` is 1). See the `tween` module
+    pub fn lerp(&self, other: &Color, t: f32) -> Color {
+        let [a, b] = [self.0, other.0];
+        Self([
+            lerp(a[0], b[0], t),
+            lerp(a[1], b[1], t),
+            lerp(a[2], b[2], t),
+            lerp(a[3], b[3], t),
+        ])
+    }
+
     pub fn unpack(&self) -> (f32, f32, f32, f32) {
         (self.0[0], self.0[1], self.0[2], self.0[3])
     }
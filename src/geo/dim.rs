@@ -3,6 +3,7 @@ use std::ops;
 /// Dimensions of a rectangle
 #[repr(C)]
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Dimensions {
     pub width: f32,
     pub height: f32,
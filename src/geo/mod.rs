@@ -1,8 +1,12 @@
+mod anchor;
 mod color;
 mod dim;
 mod point;
 mod rect;
+mod transform;
+pub use anchor::*;
 pub use color::*;
 pub use dim::*;
 pub use point::*;
 pub use rect::*;
+pub use transform::*;
@@ -1,3 +1,4 @@
+use crate::tween::lerp;
 use crate::Dimensions;
 use std::ops;
 
@@ -6,6 +7,7 @@ pub(crate) type Translation = [f32; 2];
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Point {
     pub x: f32,
     pub y: f32,
@@ -15,6 +17,15 @@ impl Point {
     pub fn to_array(&self) -> [f32; 2] {
         [self.x, self.y]
     }
+
+    /// Interpolates between `self` (when `t` is 0) and `other` (when `t` is 1).
+    /// See the `tween` module
+    pub fn lerp(&self, other: &Point, t: f32) -> Point {
+        Point {
+            x: lerp(self.x, other.x, t),
+            y: lerp(self.y, other.y, t),
+        }
+    }
 }
 
 impl<D: Into<Dimensions>> ops::Add<D> for Point {
@@ -1,3 +1,5 @@
+use crate::tween::lerp;
+use crate::Anchor;
 use crate::Point;
 
 /// Rect struct to make it more convenient to
@@ -5,6 +7,7 @@ use crate::Point;
 /// Assumes a2d coordinates (i.e. origin at upper-left
 /// corner)
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rect {
     upper_left: [f32; 2],
     lower_right: [f32; 2],
@@ -27,6 +30,21 @@ impl Rect {
         }
     }
 
+    /// Create a Rect of the given [width, height] whose `anchor`
+    /// point is at `position`.
+    /// returns None if the rectangle would be degenerate
+    /// or either dimension is negative
+    pub fn from_anchor(anchor: Anchor, position: [f32; 2], size: [f32; 2]) -> Option<Rect> {
+        let [fx, fy] = anchor.to_fraction();
+        let [width, height] = size;
+        if !(width > 0.0 && height > 0.0) {
+            return None;
+        }
+        let x1 = position[0] - fx * width;
+        let y1 = position[1] - fy * height;
+        Rect::new(x1, y1, x1 + width, y1 + height)
+    }
+
     pub const fn upper_left(&self) -> [f32; 2] {
         self.upper_left
     }
@@ -34,6 +52,77 @@ impl Rect {
     pub const fn lower_right(&self) -> [f32; 2] {
         self.lower_right
     }
+
+    /// Interpolates each corner between `self` (when `t` is 0)
+    /// and `other` (when `t` is 1). See the `tween` module
+    pub fn lerp(&self, other: &Rect, t: f32) -> Rect {
+        let x1 = lerp(self.upper_left[0], other.upper_left[0], t);
+        let y1 = lerp(self.upper_left[1], other.upper_left[1], t);
+        let x2 = lerp(self.lower_right[0], other.lower_right[0], t);
+        let y2 = lerp(self.lower_right[1], other.lower_right[1], t);
+        Self {
+            upper_left: [min(x1, x2), min(y1, y2)],
+            lower_right: [max(x1, x2), max(y1, y2)],
+        }
+    }
+
+    /// Moves (without resizing) this rect so that it lies within `bounds`.
+    /// On any axis where this rect is larger than `bounds`, it is left as is
+    pub fn clamp_to(&self, bounds: &Rect) -> Rect {
+        let mut ul = self.upper_left;
+        let mut lr = self.lower_right;
+        for i in 0..2 {
+            let size = lr[i] - ul[i];
+            if size > bounds.lower_right[i] - bounds.upper_left[i] {
+                continue;
+            }
+            let shift = if ul[i] < bounds.upper_left[i] {
+                bounds.upper_left[i] - ul[i]
+            } else if lr[i] > bounds.lower_right[i] {
+                bounds.lower_right[i] - lr[i]
+            } else {
+                0.0
+            };
+            ul[i] += shift;
+            lr[i] += shift;
+        }
+        Self {
+            upper_left: ul,
+            lower_right: lr,
+        }
+    }
+
+    /// The smallest rect containing both this rect and `other`
+    pub fn union(&self, other: &Rect) -> Rect {
+        Self {
+            upper_left: [
+                min(self.upper_left[0], other.upper_left[0]),
+                min(self.upper_left[1], other.upper_left[1]),
+            ],
+            lower_right: [
+                max(self.lower_right[0], other.lower_right[0]),
+                max(self.lower_right[1], other.lower_right[1]),
+            ],
+        }
+    }
+
+    /// Returns true if this rect and `other` overlap
+    /// (rects that only share an edge don't count)
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.upper_left[0] < other.lower_right[0]
+            && other.upper_left[0] < self.lower_right[0]
+            && self.upper_left[1] < other.lower_right[1]
+            && other.upper_left[1] < self.lower_right[1]
+    }
+
+    /// Returns the point in this rect that is closest to `p`
+    pub fn clamped_point<P: Into<Point>>(&self, p: P) -> Point {
+        let p = p.into();
+        Point {
+            x: max(self.upper_left[0], min(p.x, self.lower_right[0])),
+            y: max(self.upper_left[1], min(p.y, self.lower_right[1])),
+        }
+    }
 }
 
 impl From<[f32; 4]> for Rect {
@@ -86,3 +175,78 @@ fn max(a: f32, b: f32) -> f32 {
         b
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_anchor() {
+        let position = [10.0, 20.0];
+        let size = [4.0, 6.0];
+        let table = [
+            (Anchor::TopLeft, [10.0, 20.0, 14.0, 26.0]),
+            (Anchor::Top, [8.0, 20.0, 12.0, 26.0]),
+            (Anchor::TopRight, [6.0, 20.0, 10.0, 26.0]),
+            (Anchor::Left, [10.0, 17.0, 14.0, 23.0]),
+            (Anchor::Center, [8.0, 17.0, 12.0, 23.0]),
+            (Anchor::Right, [6.0, 17.0, 10.0, 23.0]),
+            (Anchor::BottomLeft, [10.0, 14.0, 14.0, 20.0]),
+            (Anchor::Bottom, [8.0, 14.0, 12.0, 20.0]),
+            (Anchor::BottomRight, [6.0, 14.0, 10.0, 20.0]),
+        ];
+        for &(anchor, expected) in &table {
+            assert_eq!(
+                Rect::from_anchor(anchor, position, size),
+                Some(Rect::from(expected)),
+                "{:?}",
+                anchor,
+            );
+        }
+    }
+
+    #[test]
+    fn from_anchor_degenerate() {
+        for &size in &[[0.0, 6.0], [4.0, 0.0], [-4.0, 6.0], [4.0, -6.0]] {
+            assert_eq!(Rect::from_anchor(Anchor::Center, [0.0, 0.0], size), None);
+        }
+    }
+
+    #[test]
+    fn clamp_to() {
+        let bounds = Rect::from([0.0, 0.0, 100.0, 50.0]);
+        let clamp = |r: [f32; 4]| Rect::from(r).clamp_to(&bounds);
+
+        // Already inside
+        assert_eq!(
+            clamp([10.0, 10.0, 20.0, 20.0]),
+            [10.0, 10.0, 20.0, 20.0].into()
+        );
+
+        // Past the left/top edges
+        assert_eq!(
+            clamp([-5.0, 10.0, 5.0, 20.0]),
+            [0.0, 10.0, 10.0, 20.0].into()
+        );
+        assert_eq!(
+            clamp([10.0, -8.0, 20.0, 2.0]),
+            [10.0, 0.0, 20.0, 10.0].into()
+        );
+
+        // Past the right/bottom edges
+        assert_eq!(
+            clamp([95.0, 10.0, 105.0, 20.0]),
+            [90.0, 10.0, 100.0, 20.0].into()
+        );
+        assert_eq!(
+            clamp([10.0, 45.0, 20.0, 55.0]),
+            [10.0, 40.0, 20.0, 50.0].into()
+        );
+
+        // Wider than the bounds: x is left as is, but y is still clamped
+        assert_eq!(
+            clamp([-10.0, 45.0, 110.0, 55.0]),
+            [-10.0, 40.0, 110.0, 50.0].into()
+        );
+    }
+}
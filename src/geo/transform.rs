@@ -0,0 +1,48 @@
+/// A translation, rotation and scale that can be applied on top of
+/// another transformation (e.g. a batch's own translation and scale)
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transform2D {
+    pub translation: [f32; 2],
+
+    /// Clockwise rotation in radians
+    pub rotation: f32,
+
+    pub scale: [f32; 2],
+}
+
+impl Transform2D {
+    /// Leaves everything unchanged
+    pub const IDENTITY: Transform2D = Transform2D {
+        translation: [0.0, 0.0],
+        rotation: 0.0,
+        scale: [1.0, 1.0],
+    };
+
+    pub fn translation(translation: [f32; 2]) -> Transform2D {
+        Transform2D {
+            translation,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn rotation(rotation: f32) -> Transform2D {
+        Transform2D {
+            rotation,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn scale(scale: [f32; 2]) -> Transform2D {
+        Transform2D {
+            scale,
+            ..Self::IDENTITY
+        }
+    }
+}
+
+impl Default for Transform2D {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
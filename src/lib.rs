@@ -10,6 +10,8 @@ mod geo;
 mod res;
 mod shaders;
 
+pub mod tween;
+
 pub use error::*;
 pub use g2d::*;
 pub use geo::*;
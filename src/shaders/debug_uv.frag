@@ -0,0 +1,10 @@
+// debug_uv.frag
+#version 450
+
+layout(location=0) in vec2 v_tex_coords;
+layout(location=1) in vec4 v_color_factor;
+layout(location=0) out vec4 f_color;
+
+void main() {
+    f_color = vec4(v_tex_coords, 0.0, 1.0);
+}
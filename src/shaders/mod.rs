@@ -20,3 +20,14 @@ macro_rules! get_bytes {
 
 pub const VERT: &[u8] = get_bytes!("shader.vert.spirv");
 pub const FRAG: &[u8] = get_bytes!("shader.frag.spirv");
+
+/// Fragment shader for MultiSheetBatch, which picks one of several sheets per sprite
+pub const MULTI_FRAG: &[u8] = get_bytes!("multi.frag.spirv");
+
+/// Fragment shader for batches with a palette, which looks up the color of
+/// each pixel in the palette using the pixel's red channel as the index
+pub const PALETTE_FRAG: &[u8] = get_bytes!("palette.frag.spirv");
+
+/// Fragment shaders for DebugMode::ShowUv and DebugMode::ShowOverdraw
+pub const DEBUG_UV_FRAG: &[u8] = get_bytes!("debug_uv.frag.spirv");
+pub const OVERDRAW_FRAG: &[u8] = get_bytes!("overdraw.frag.spirv");
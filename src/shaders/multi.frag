@@ -0,0 +1,36 @@
+// multi.frag
+#version 450
+
+layout(location=0) in vec2 v_tex_coords;
+layout(location=1) in vec4 v_color_factor;
+layout(location=2) flat in int v_sheet_index;
+layout(location=0) out vec4 f_color;
+
+// One binding per sheet (see MAX_SHEETS_PER_BATCH)
+layout(set = 0, binding = 0) uniform texture2D t_sheet0;
+layout(set = 0, binding = 1) uniform texture2D t_sheet1;
+layout(set = 0, binding = 2) uniform texture2D t_sheet2;
+layout(set = 0, binding = 3) uniform texture2D t_sheet3;
+layout(set = 0, binding = 4) uniform sampler s_diffuse;
+
+void main() {
+    // Indexing an array of textures with a value that differs per sprite
+    // isn't supported everywhere, so branch instead
+    vec4 color;
+    if (v_sheet_index == 0) {
+        color = texture(sampler2D(t_sheet0, s_diffuse), v_tex_coords);
+    } else if (v_sheet_index == 1) {
+        color = texture(sampler2D(t_sheet1, s_diffuse), v_tex_coords);
+    } else if (v_sheet_index == 2) {
+        color = texture(sampler2D(t_sheet2, s_diffuse), v_tex_coords);
+    } else {
+        color = texture(sampler2D(t_sheet3, s_diffuse), v_tex_coords);
+    }
+    f_color = color * v_color_factor;
+
+    // Fully transparent pixels shouldn't hide what's behind them
+    // when depth testing is enabled
+    if (f_color.a == 0.0) {
+        discard;
+    }
+}
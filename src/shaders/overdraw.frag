@@ -0,0 +1,12 @@
+// overdraw.frag
+#version 450
+
+layout(location=0) in vec2 v_tex_coords;
+layout(location=1) in vec4 v_color_factor;
+layout(location=0) out vec4 f_color;
+
+// Drawn with additive blending, so each sprite covering
+// a pixel makes it a bit brighter
+void main() {
+    f_color = vec4(0.1, 0.1, 0.1, 1.0);
+}
@@ -0,0 +1,33 @@
+// palette.frag
+#version 450
+
+layout(location=0) in vec2 v_tex_coords;
+layout(location=1) in vec4 v_color_factor;
+layout(location=0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform texture2D t_diffuse;
+layout(set = 0, binding = 1) uniform sampler s_diffuse;
+
+// A single row of colors, one per palette index (see Graphics2D::set_batch_palette)
+layout(set = 3, binding = 0) uniform texture2D t_palette;
+layout(set = 3, binding = 1) uniform sampler s_palette;
+
+// Sheets are stored as sRGB, so the red channel arrives already converted
+// to linear, and has to be converted back to get the index that was stored
+float linear_to_srgb(float c) {
+    return c <= 0.0031308 ? c * 12.92 : 1.055 * pow(c, 1.0 / 2.4) - 0.055;
+}
+
+void main() {
+    vec4 texel = texture(sampler2D(t_diffuse, s_diffuse), v_tex_coords);
+    float index = floor(linear_to_srgb(texel.r) * 255.0 + 0.5);
+    float palette_width = float(textureSize(sampler2D(t_palette, s_palette), 0).x);
+    vec4 color = texture(sampler2D(t_palette, s_palette), vec2((index + 0.5) / palette_width, 0.5));
+    f_color = vec4(color.rgb, color.a * texel.a) * v_color_factor;
+
+    // Fully transparent pixels shouldn't hide what's behind them
+    // when depth testing is enabled
+    if (f_color.a == 0.0) {
+        discard;
+    }
+}
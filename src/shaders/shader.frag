@@ -10,4 +10,10 @@ layout(set = 0, binding = 1) uniform sampler s_diffuse;
 
 void main() {
     f_color = texture(sampler2D(t_diffuse, s_diffuse), v_tex_coords) * v_color_factor;
+
+    // Fully transparent pixels shouldn't hide what's behind them
+    // when depth testing is enabled
+    if (f_color.a == 0.0) {
+        discard;
+    }
 }
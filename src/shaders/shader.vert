@@ -7,17 +7,35 @@ layout(location=2) in vec2 dst_ul;
 layout(location=3) in vec2 dst_lr;
 layout(location=4) in float rotate_theta;
 layout(location=5) in vec4 color_factor;
+layout(location=6) in float z;
+layout(location=7) in float sheet_index;
+layout(location=8) in vec4 corner_color_ul;
+layout(location=9) in vec4 corner_color_ur;
+layout(location=10) in vec4 corner_color_lr;
+layout(location=11) in vec4 corner_color_ll;
+layout(location=12) in vec2 uv_offset;
+layout(location=13) in vec2 uv_scale;
 
 layout(location=0) out vec2 v_tex_coords;
 layout(location=1) out vec4 v_color_factor;
+// only used by multi.frag
+layout(location=2) flat out int v_sheet_index;
 
 layout(set = 1, binding = 0) uniform Uniform {
     vec2 u_scale;
+    vec2 u_framebuffer_size;
+    float u_pixel_snapping;
+    float u_y_up;
+    float u_quarter_turns;
 };
 
 layout(set = 2, binding = 0) uniform TranslationUniform {
     vec2 u_per_batch_scale;
     vec2 u_per_batch_translate;
+    vec4 u_per_batch_tint;
+    vec2 u_per_batch_rotation_origin;
+    float u_per_batch_rotation;
+    vec4 u_per_batch_color_override;
 };
 
 const vec2 positions[4] = vec2[4](
@@ -27,11 +45,6 @@ const vec2 positions[4] = vec2[4](
     vec2(0.0, 1.0)
 );
 
-const uint indices[6] = uint[6](
-    0, 3, 2,
-    0, 2, 1
-);
-
 // matrix to multiply to get wgpu coordinates
 const mat3 to_wgpu = mat3(
     2.0, 0.0, 0.0,
@@ -64,9 +77,26 @@ const mat3 normalized_basis = mat3(
 );
 
 void main() {
-    // Just pass color_factor to fragment shader; there isn't any
-    // processing to be done for it in the vertex shader
-    v_color_factor = color_factor;
+    // The corners are in the same order as positions, and the fragment
+    // shader interpolates between them
+    vec4 corner_colors[4] = vec4[4](
+        corner_color_ul,
+        corner_color_ur,
+        corner_color_lr,
+        corner_color_ll
+    );
+
+    // The per-batch tint applies to every sprite in the batch, so it can
+    // just be folded into each sprite's color_factor here rather than
+    // per fragment
+    v_color_factor = color_factor * corner_colors[gl_VertexIndex] * u_per_batch_tint;
+    if (u_per_batch_color_override.a != 0.0) {
+        v_color_factor = vec4(
+            u_per_batch_color_override.rgb,
+            u_per_batch_color_override.a * v_color_factor.a
+        );
+    }
+    v_sheet_index = int(sheet_index);
 
     // ---------------
     // Define some useful matrices for the
@@ -104,21 +134,50 @@ void main() {
     // ---------------
     // now compute actual coordinates
     // ---------------
-    vec2 normalized_pos2 = positions[indices[gl_VertexIndex]];
+    // Each sprite is drawn as 4 vertices, with the 2 triangles
+    // assembled from the index buffer
+    vec2 normalized_pos2 = positions[gl_VertexIndex];
     vec3 normalized_pos3 = vec3(normalized_pos2, 1.0);
 
     vec3 src_pos3 = normalized_to_src * normalized_pos3;
     vec3 dst_pos3 = normalized_to_dst * normalized_pos3;
     vec3 rot_pos3 = rotate_around_dst_center * dst_pos3;
-    vec3 translated_pos3 = vec3(
+    mat3 batch_rotation =
+        translation_matrix(u_per_batch_rotation_origin) *
+        rotation_matrix_around_origin(u_per_batch_rotation) *
+        translation_matrix(-u_per_batch_rotation_origin);
+    vec3 translated_pos3 = batch_rotation * vec3(
         vec2(rot_pos3) * u_per_batch_scale + u_per_batch_translate,
         1.0
     );
 
+    vec3 normalized_screen_pos3 = translated_pos3 / vec3(u_scale, 1.0);
+    if (u_y_up != 0.0) {
+        normalized_screen_pos3.y = 1.0 - normalized_screen_pos3.y;
+    }
+    // rotate the whole output clockwise around the center of the screen
+    vec2 p = normalized_screen_pos3.xy;
+    if (u_quarter_turns == 1.0) {
+        normalized_screen_pos3.xy = vec2(1.0 - p.y, p.x);
+    } else if (u_quarter_turns == 2.0) {
+        normalized_screen_pos3.xy = vec2(1.0 - p.x, 1.0 - p.y);
+    } else if (u_quarter_turns == 3.0) {
+        normalized_screen_pos3.xy = vec2(p.y, 1.0 - p.x);
+    }
+    vec2 wgpu_pos2 = vec2(to_wgpu * normalized_screen_pos3);
+    if (u_pixel_snapping != 0.0) {
+        // round to the nearest pixel of the framebuffer
+        vec2 pixel_pos2 = round((wgpu_pos2 + 1.0) / 2.0 * u_framebuffer_size);
+        wgpu_pos2 = pixel_pos2 / u_framebuffer_size * 2.0 - 1.0;
+    }
+
     v_tex_coords = vec2(src_pos3);
-    gl_Position = vec4(
-        vec2(to_wgpu * (translated_pos3 / vec3(u_scale, 1.0))),
-        0.0,
-        1.0
-    );
+    if (u_y_up != 0.0) {
+        // The upper edge of dst is now at the bottom of the screen,
+        // so flip the source as well to keep the sheet right side up
+        v_tex_coords.y = src_ul.y + src_lr.y - v_tex_coords.y;
+    }
+    v_tex_coords = v_tex_coords * uv_scale + uv_offset;
+    // Anything outside of [0, 1] would get clipped, so clamp instead
+    gl_Position = vec4(wgpu_pos2, clamp(z, 0.0, 1.0), 1.0);
 }
@@ -0,0 +1,56 @@
+//! Helpers for animating values over time.
+//!
+//! The easing functions all map [0, 1] onto [0, 1], and are meant to be
+//! applied to `t` before interpolating, e.g.
+//! `rect_a.lerp(&rect_b, tween::ease_in_out(t))`.
+use std::f32::consts::PI;
+
+/// Linearly interpolates between `a` (when `t` is 0) and `b` (when `t` is 1)
+pub fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// Interpolates between two angles in radians, going whichever way
+/// around the circle is shorter
+pub fn lerp_angle(a: f32, b: f32, t: f32) -> f32 {
+    let diff = (b - a).rem_euclid(2.0 * PI);
+    let diff = if diff > PI { diff - 2.0 * PI } else { diff };
+    a + diff * t
+}
+
+pub fn linear(t: f32) -> f32 {
+    t
+}
+
+pub fn ease_in_quad(t: f32) -> f32 {
+    t * t
+}
+
+pub fn ease_out_quad(t: f32) -> f32 {
+    1.0 - (1.0 - t) * (1.0 - t)
+}
+
+/// Starts slow, speeds up in the middle and slows down again at the end
+pub fn ease_in_out(t: f32) -> f32 {
+    if t < 0.5 {
+        2.0 * t * t
+    } else {
+        1.0 - (-2.0 * t + 2.0).powi(2) / 2.0
+    }
+}
+
+pub fn ease_in_cubic(t: f32) -> f32 {
+    t * t * t
+}
+
+pub fn ease_out_cubic(t: f32) -> f32 {
+    1.0 - (1.0 - t).powi(3)
+}
+
+pub fn ease_in_out_cubic(t: f32) -> f32 {
+    if t < 0.5 {
+        4.0 * t * t * t
+    } else {
+        1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+    }
+}